@@ -0,0 +1,179 @@
+#ifndef MINIGREP_H
+#define MINIGREP_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ Result of every fallible call. On failure, the message is available
+ from `minigrep_search_last_error`.
+ */
+typedef enum MinigrepStatus {
+  MINIGREP_STATUS_OK = 0,
+  /*
+   A required pointer was null, or a string was not UTF-8.
+   */
+  MINIGREP_STATUS_INVALID_ARGUMENT = 1,
+  /*
+   The pattern does not compile.
+   */
+  MINIGREP_STATUS_INVALID_PATTERN = 2,
+  /*
+   The input could not be read.
+   */
+  MINIGREP_STATUS_IO = 3,
+  /*
+   Any other failure.
+   */
+  MINIGREP_STATUS_FAILED = 4,
+  /*
+   The search panicked. The handle may be used again.
+   */
+  MINIGREP_STATUS_PANIC = 5,
+} MinigrepStatus;
+
+/*
+ A compiled search and its options. Create with `minigrep_search_new`,
+ release with `minigrep_free`. A handle must not be used from two
+ threads at once.
+ */
+typedef struct MinigrepSearch MinigrepSearch;
+
+/*
+ One occurrence of the pattern within a matched line.
+ */
+typedef struct MinigrepSpan {
+  size_t start;
+  size_t end;
+} MinigrepSpan;
+
+/*
+ A matched line, passed to the match callback. Every pointer is valid
+ only for the duration of the callback.
+ */
+typedef struct MinigrepMatch {
+  /*
+   NUL-terminated path or label of the input.
+   */
+  const char *path;
+  size_t line_number;
+  /*
+   Byte offset of the start of the line within its input.
+   */
+  uint64_t byte_offset;
+  /*
+   The line without its terminator: `line_len` bytes of UTF-8, not
+   NUL-terminated.
+   */
+  const char *line;
+  size_t line_len;
+  /*
+   Byte ranges of each occurrence within `line`.
+   */
+  const struct MinigrepSpan *spans;
+  size_t spans_len;
+} MinigrepMatch;
+
+/*
+ Called for each matched line with the `user_data` given to the search.
+ Return `true` to continue or `false` to stop the search early, which
+ still counts as success.
+ */
+typedef bool (*MinigrepMatchCallback)(const struct MinigrepMatch *found, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Creates a search for `pattern`, a NUL-terminated UTF-8 regular
+ expression. Returns null if `pattern` is null or not UTF-8; a pattern
+ that does not compile is reported by the first search.
+
+ # Safety
+
+ `pattern` must be null or point to a NUL-terminated string.
+ */
+struct MinigrepSearch *minigrep_search_new(const char *pattern);
+
+/*
+ # Safety
+
+ `search` must be null or a live handle from `minigrep_search_new`.
+ */
+enum MinigrepStatus minigrep_search_set_ignore_case(struct MinigrepSearch *search, bool yes);
+
+/*
+ # Safety
+
+ `search` must be null or a live handle from `minigrep_search_new`.
+ */
+enum MinigrepStatus minigrep_search_set_invert_match(struct MinigrepSearch *search, bool yes);
+
+/*
+ # Safety
+
+ `search` must be null or a live handle from `minigrep_search_new`.
+ */
+enum MinigrepStatus minigrep_search_set_only_matching(struct MinigrepSearch *search, bool yes);
+
+/*
+ Searches a file, or every searchable file below a directory, calling
+ `callback` for each matched line.
+
+ # Safety
+
+ `search` must be null or a live handle from `minigrep_search_new`,
+ `path` null or a NUL-terminated string, and `callback` must be safe to
+ call with `user_data`.
+ */
+enum MinigrepStatus minigrep_search_path(struct MinigrepSearch *search,
+                                         const char *path,
+                                         MinigrepMatchCallback callback,
+                                         void *user_data);
+
+/*
+ Searches `len` bytes at `data`, reporting matches under `label`.
+
+ # Safety
+
+ As for `minigrep_search_path`, with `label` in place of `path`; `data`
+ must point to `len` readable bytes, or may be null when `len` is 0.
+ */
+enum MinigrepStatus minigrep_search_buffer(struct MinigrepSearch *search,
+                                           const char *label,
+                                           const uint8_t *data,
+                                           size_t len,
+                                           MinigrepMatchCallback callback,
+                                           void *user_data);
+
+/*
+ The message for the last failed call on `search`, or null if the last
+ call succeeded. Valid until the next call on the same handle.
+
+ # Safety
+
+ `search` must be null or a live handle from `minigrep_search_new`.
+ */
+const char *minigrep_search_last_error(const struct MinigrepSearch *search);
+
+/*
+ Releases a handle. Passing null does nothing.
+
+ # Safety
+
+ `search` must be null or a live handle from `minigrep_search_new`, and
+ is dangling afterwards.
+ */
+void minigrep_free(struct MinigrepSearch *search);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MINIGREP_H */
@@ -1,72 +1,233 @@
 // src/app.rs
 
-use std::collections::HashSet;
-use std::error::Error;
-use std::io;
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use regex::Regex;
 use walkdir::WalkDir;
 
 use crate::config::{Config, OutputMode};
-use crate::search::SearcherBuilder;
+use crate::error::{Diagnostics, Error, Result, StderrDiagnostics};
+use crate::fs::{FileSort, SortKey};
+use crate::output::colors::ColorScheme;
+use crate::rewrite::{interactive::InteractiveRewriter, Rewriter};
+use crate::output::{builder::SinkBuilder, Sink};
+use crate::search::{control::{Progress, ProgressListener}, options::SearchOptions, stats::cpu_time, Searcher};
 pub struct App<'a> {
     config: &'a Config,
+    /// Paths to search; stdin when empty.
+    paths: Vec<PathBuf>,
+    options: SearchOptions,
     re: &'a Regex,
     output_mode: OutputMode,
+    colors: ColorScheme,
 }
 
 impl<'a> App<'a> {
-    pub fn new(config: &'a Config, re: &'a Regex, output_mode: OutputMode) -> Self {
-        Self { config, re, output_mode }
+    pub fn new(
+        config: &'a Config,
+        paths: Vec<PathBuf>,
+        options: SearchOptions,
+        re: &'a Regex,
+        output_mode: OutputMode,
+        colors: ColorScheme,
+    ) -> Self {
+        Self { config, paths, options, re, output_mode, colors }
     }
 
-    pub fn execute(&self) -> Result<(), Box<dyn Error>> {
-        let (after_len, before_len) = self.config.get_effective_context();
+    pub fn execute(&self) -> Result<()> {
+        if let Some(template) = &self.config.rewrite.rewrite {
+            return self.rewrite(template);
+        }
+        if let Some(template) = &self.config.rewrite.interactive_replace {
+            return self.interactive_rewrite(template);
+        }
 
-        if let Some(path_str) = &self.config.path {
-            let path = Path::new(path_str);
-            
+        let started = Instant::now();
+        let mut searcher = Searcher::with_matcher(self.options.matcher(self.re.clone()), self.options.clone());
+        // The status line is only drawn where someone can watch it.
+        let progress = self.config.output.progress && io::stderr().is_terminal();
+        if self.config.output.no_messages {
+            searcher = searcher.diagnostics(|_: &Error| {});
+        } else if progress {
+            searcher = searcher.diagnostics(|e: &Error| {
+                ProgressLine::clear();
+                eprintln!("{}", e);
+            });
+        }
+        if progress {
+            searcher = searcher.progress(ProgressLine::default());
+        }
+
+        let result = self.search(&mut searcher, started);
+        if progress {
+            ProgressLine::clear();
+        }
+        result
+    }
+
+    fn search(&self, searcher: &mut Searcher, started: Instant) -> Result<()> {
+        if !self.paths.is_empty() {
             let all_files = if self.output_mode == OutputMode::FilesWithoutMatch {
-                Some(self.collect_all_files(path))
+                Some(self.paths.iter().flat_map(|path| self.collect_all_files(path)).collect())
             } else {
                 None
             };
 
-            let mut searcher = SearcherBuilder::new(self.config, self.re)
-                .build(self.output_mode, all_files);
+            let mut sink = SinkBuilder::new(self.config, self.re, &self.colors)
+                .build(self.output_mode, all_files)?;
             
-            searcher.search_path(path, before_len, after_len)?;
-            searcher.sink.finish();
+            for path in &self.paths {
+                searcher.search_path(path, sink.as_mut())?;
+            }
+            self.finish(searcher, sink.as_mut(), started)?;
 
         } else {
             if self.output_mode == OutputMode::FilesWithoutMatch {
-                return Err("Error: --files-without-match is not supported for stdin.".into());
+                return Err(Error::Unsupported("--files-without-match is not supported for stdin".into()));
             }
 
-            let mut searcher = SearcherBuilder::new(self.config, self.re)
-                .build(self.output_mode, None);
+            let mut sink = SinkBuilder::new(self.config, self.re, &self.colors)
+                .build(self.output_mode, None)?;
             
             let stdin = io::stdin();
             let reader = stdin.lock();
-            searcher.search_reader(reader, before_len, after_len)?;
-            searcher.sink.finish();
+            searcher.search_reader("stdin", reader, sink.as_mut())?;
+            self.finish(searcher, sink.as_mut(), started)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&self, searcher: &Searcher, sink: &mut dyn Sink, started: Instant) -> Result<()> {
+        if !self.config.output.stats {
+            return sink.finish().map_err(Error::Sink);
+        }
+
+        let mut stats = searcher.stats().clone();
+        stats.elapsed_wall = started.elapsed();
+        stats.elapsed_cpu = cpu_time();
+
+        let reported = sink.stats(&stats);
+        sink.finish().map_err(Error::Sink)?;
+
+        if !reported {
+            // Keep delimited output loadable by sending the summary elsewhere.
+            if matches!(self.output_mode, OutputMode::Csv | OutputMode::Tsv) {
+                eprintln!("\n{}", stats);
+            } else {
+                writeln!(io::stdout(), "\n{}", stats).map_err(|e| Error::io("stdout", e))?;
+            }
         }
+        Ok(())
+    }
+
+    fn rewrite(&self, template: &str) -> Result<()> {
+        let Some(path_str) = &self.config.path else {
+            return Err(Error::Unsupported("--rewrite is not supported for stdin".into()));
+        };
+
+        let dry_run = self.config.rewrite.dry_run;
+        let rewriter = Rewriter::new(
+            self.re,
+            template,
+            self.config.rewrite.backup.as_deref(),
+            dry_run,
+        );
+        let mut out = io::stdout().lock();
+        let summary = rewriter.rewrite_path(Path::new(path_str), &mut out, self.diagnostics().as_mut())?;
+        out.flush().map_err(|e| Error::io("stdout", e))?;
+
+        let verb = if dry_run { "Would rewrite" } else { "Rewrote" };
+        writeln!(
+            io::stderr(),
+            "{} {} file(s), {} replacement(s)",
+            verb, summary.files_changed, summary.replacements
+        )
+        .map_err(|e| Error::io("stderr", e))
+    }
+
+    fn interactive_rewrite(&self, template: &str) -> Result<()> {
+        let Some(path_str) = &self.config.path else {
+            return Err(Error::Unsupported("--interactive-replace is not supported for stdin".into()));
+        };
 
+        let (after_len, before_len) = match self.config.get_effective_context() {
+            (0, 0) => (2, 2),
+            context => context,
+        };
+        let rewriter = InteractiveRewriter::new(
+            self.re,
+            template,
+            self.config.rewrite.backup.as_deref(),
+            before_len,
+            after_len,
+        );
+        let summary = rewriter.rewrite_path(Path::new(path_str), self.diagnostics().as_mut())?;
+
+        let mut err = io::stderr().lock();
+        writeln!(
+            err,
+            "Accepted {} edit(s), skipped {} edit(s), {} file(s) changed",
+            summary.accepted, summary.skipped, summary.files_changed
+        )
+        .map_err(|e| Error::io("stderr", e))?;
+        if summary.not_reviewed > 0 {
+            writeln!(err, "{} match(es) not reviewed", summary.not_reviewed).map_err(|e| Error::io("stderr", e))?;
+        }
         Ok(())
     }
 
-    fn collect_all_files(&self, path: &Path) -> HashSet<PathBuf> {
+    /// Where per-file problems go: stderr, or nowhere with `--no-messages`.
+    fn diagnostics(&self) -> Box<dyn Diagnostics> {
+        if self.config.output.no_messages {
+            Box::new(|_: &Error| {})
+        } else {
+            Box::new(StderrDiagnostics)
+        }
+    }
+
+    /// Every file under `path`, in `--sort` order or by path by default.
+    fn collect_all_files(&self, path: &Path) -> Vec<PathBuf> {
         let walker = if path.is_dir() {
             WalkDir::new(path).min_depth(1).into_iter()
         } else {
             WalkDir::new(path).max_depth(0).into_iter()
         };
         
-        walker
+        let mut files: Vec<PathBuf> = walker
             .filter_map(|e| e.ok())
             .filter(|e| e.file_type().is_file())
             .map(|e| e.path().to_path_buf())
-            .collect::<HashSet<PathBuf>>()
+            .collect();
+
+        let sort = self.config.file_sort().unwrap_or(FileSort { key: SortKey::Path, reverse: false });
+        sort.sort(&mut files);
+        files
+    }
+}
+/// `--progress`: a status line on stderr, redrawn at most every 100ms when
+/// stderr is a terminal.
+#[derive(Default)]
+struct ProgressLine {
+    drawn: Option<Instant>,
+}
+
+impl ProgressLine {
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn clear() {
+        eprint!("\r\x1b[K");
+    }
+}
+
+impl ProgressListener for ProgressLine {
+    fn progress(&mut self, progress: &Progress) {
+        if self.drawn.is_some_and(|drawn| drawn.elapsed() < Self::INTERVAL) {
+            return;
+        }
+        self.drawn = Some(Instant::now());
+        eprint!("\r\x1b[K{} files, {} bytes searched", progress.files_searched, progress.bytes_searched);
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,10 @@
-use clap::{Args, Parser};
+use std::{env, io::{self, IsTerminal}};
+
+use clap::{ArgGroup, Args, Parser, ValueEnum};
+
+use crate::fs::{FileSort, SortKey};
+use crate::search::options::SearchOptions;
+use crate::output::{colors::ColorScheme, sarif::SarifLevel, tally::TallyOrder};
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -8,32 +14,136 @@ pub enum OutputMode {
     Count,
     FilesWithMatches,
     FilesWithoutMatch,
+    Template,
+    Sarif,
+    Csv,
+    Tsv,
+    Tally,
+    GroupBy,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolves whether escape codes should be written. Structured output
+    /// modes are never colored; `auto` honors `NO_COLOR` and `CLICOLOR_FORCE`
+    /// before falling back to checking whether stdout is a terminal.
+    pub fn should_colorize(self, mode: OutputMode) -> bool {
+        if matches!(mode, OutputMode::Json | OutputMode::Sarif | OutputMode::Csv | OutputMode::Tsv) {
+            return false;
+        }
+
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let env_set = |name: &str| env::var_os(name).is_some_and(|v| !v.is_empty() && v != "0");
+                if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                    false
+                } else if env_set("CLICOLOR_FORCE") {
+                    true
+                } else {
+                    io::stdout().is_terminal()
+                }
+            }
+        }
+    }
 }
 
 #[derive(Args, Debug, Clone)]
 #[command(next_help_heading = "Output Modes")]
-#[group(id = "output_mode_flags", multiple = false)]
+#[command(group(
+    ArgGroup::new("output_mode_flags")
+        .args(["count", "files_with_matches", "files_without_match", "format", "sarif", "csv", "tsv", "tally", "group_by"])
+        .multiple(false)
+))]
+#[command(group(ArgGroup::new("counting").args(["count", "count_matches"]).multiple(true)))]
 pub struct OutputModeArgs {
     #[arg(long, 
         help = "Output results in JSON format", 
-        group = "output_mode_flags",
-        conflicts_with_all = &["files_with_matches", "count", "files_without_match"]
+        conflicts_with_all = &["files_with_matches", "files_without_match", "format", "sarif", "csv", "tsv"]
     )]
     pub json: bool,
 
-    #[arg(short, long, help = "Print a count of matching lines", group = "output_mode_flags")]
+    #[arg(short, long, help = "Print a count of matching lines")]
     pub count: bool,
 
-    #[arg(short = 'l', long, help = "Print only the names of files with matches", group = "output_mode_flags")]
+    #[arg(
+        long,
+        help = "Print a count of individual matches rather than matching lines; implies --count",
+        conflicts_with_all = &["files_with_matches", "files_without_match", "format", "sarif", "csv", "tsv", "tally", "group_by"]
+    )]
+    pub count_matches: bool,
+
+    #[arg(
+        long,
+        help = "With --count or --count-matches, also list searched files with no matches",
+        requires = "counting"
+    )]
+    pub include_zero: bool,
+
+    #[arg(short = 'l', long, help = "Print only the names of files with matches")]
     pub files_with_matches: bool,
 
     #[arg(
         long, 
         help = "Print only the names of files that DO NOT contain matches",
-        group = "output_mode_flags",
-        conflicts_with_all = &["json", "count", "files_with_matches"]
+        conflicts_with_all = &["json", "files_with_matches", "format", "sarif", "csv", "tsv"]
     )]
     pub files_without_match: bool,
+
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "Print each match using TEMPLATE, e.g. '{path}\\t{line}\\t{col}\\t{match}'",
+        long_help = "Print each match using TEMPLATE. Placeholders: {path}, {basename}, {line}, {col} \
+(1-based, counted in characters), {offset} (in bytes from the start of the file), {text} (the whole line), {match}, {pattern}, and capture groups by index ({1}) or name ({name}). \
+Templates using {match}, {col}, {offset} or a capture group print once per match, otherwise once per line. \
+Write {{ and }} for literal braces; \\t, \\n, \\r and \\\\ are unescaped."
+    )]
+    pub format: Option<String>,
+
+    #[arg(long, help = "Output results as a SARIF 2.1.0 log for code-scanning tools")]
+    pub sarif: bool,
+
+    #[arg(long, help = "Output one CSV row per match (path, line, column, match, text); columns count characters from 1")]
+    pub csv: bool,
+
+    #[arg(long, help = "Output one TSV row per match (path, line, column, match, text); columns count characters from 1")]
+    pub tsv: bool,
+
+    #[arg(
+        long,
+        help = "Print a frequency table of matched text across all files",
+        conflicts_with = "invert_match"
+    )]
+    pub tally: bool,
+
+    #[arg(long, value_name = "GROUP", requires = "tally", help = "Tally a capture group (by name or number) instead of the whole match")]
+    pub tally_group: Option<String>,
+
+    #[arg(long, value_name = "N", requires = "tally", help = "Only print the N most (or least) frequent values")]
+    pub top: Option<usize>,
+
+    #[arg(long, value_enum, value_name = "ORDER", default_value_t = TallyOrder::Desc, requires = "tally", help = "Order the tally by count")]
+    pub tally_order: TallyOrder,
+
+    #[arg(
+        long,
+        value_name = "GROUP",
+        help = "Count matches keyed by a capture group; repeat to group by several",
+        conflicts_with = "invert_match"
+    )]
+    pub group_by: Vec<String>,
+
+    #[arg(long, value_name = "GROUP", requires = "group_by", help = "With --group-by, also sum a numeric capture group; values that are not numbers are counted apart")]
+    pub sum: Option<String>,
 }
 
 impl TryFrom<&OutputModeArgs> for OutputMode {
@@ -41,10 +151,16 @@ impl TryFrom<&OutputModeArgs> for OutputMode {
 
     fn try_from(args: &OutputModeArgs) -> Result<Self, Self::Error> {
         let dispatch_table = vec![
+            (args.count || args.count_matches, OutputMode::Count),
+            (args.tally, OutputMode::Tally),
+            (!args.group_by.is_empty(), OutputMode::GroupBy),
             (args.json, OutputMode::Json),
-            (args.count, OutputMode::Count),
             (args.files_with_matches, OutputMode::FilesWithMatches),
             (args.files_without_match, OutputMode::FilesWithoutMatch),
+            (args.format.is_some(), OutputMode::Template),
+            (args.sarif, OutputMode::Sarif),
+            (args.csv, OutputMode::Csv),
+            (args.tsv, OutputMode::Tsv),
 
         ];
 
@@ -61,19 +177,33 @@ impl TryFrom<&OutputModeArgs> for OutputMode {
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Config {
-    #[arg(help = "The pattern to search for")]
-    pub query: String,
+    #[arg(help = "The pattern to search for", required_unless_present_any = ["request", "serve"])]
+    pub query: Option<String>,
     #[arg(help = "The path to the file to search in")]
     pub path: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        exclusive = true,
+        help = "Run the search described by a JSON request in FILE ('-' for stdin) and print JSON results"
+    )]
+    pub request: Option<String>,
+    #[arg(
+        long,
+        exclusive = true,
+        help = "Answer JSON-RPC search requests on stdin, one message per line, until shut down"
+    )]
+    pub serve: bool,
     #[command(flatten)]
     pub search: SearchOption,
     #[command(flatten)]
     pub output: OutputOption,
     #[command(flatten)]
     pub mode_args: OutputModeArgs,
+    #[command(flatten)]
+    pub rewrite: RewriteOption,
 }
 
-
 #[derive(Args, Debug, Clone)]
 #[command(next_help_heading = "Search Options")]
 pub struct SearchOption {
@@ -96,9 +226,117 @@ pub struct OutputOption {
     pub context: usize,
     #[arg(short = 'n', long, help = "Prefix each line of output with the line number")]
     pub line_number: bool,
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        requires = "format",
+        help = "Print context lines using TEMPLATE ({path}, {basename}, {line} and {text} only)"
+    )]
+    pub context_format: Option<String>,
+    #[arg(long, help = "Print statistics about the search after the results")]
+    pub stats: bool,
+    #[arg(long, value_name = "FILE", help = "Also write the results as JSON to FILE")]
+    pub json_out: Option<String>,
+    #[arg(long, value_name = "FILE", help = "Also write the results as a SARIF log to FILE")]
+    pub sarif_out: Option<String>,
+    #[arg(long, value_name = "FILE", help = "Also write the results as CSV to FILE")]
+    pub csv_out: Option<String>,
+    #[arg(long, value_name = "FILE", help = "Also write a self-contained HTML report of the results to FILE")]
+    pub html_report: Option<String>,
+    #[arg(long, value_enum, value_name = "LEVEL", default_value_t = SarifLevel::Warning, help = "Severity reported for each rule in SARIF output")]
+    pub sarif_level: SarifLevel,
+    #[arg(long, value_enum, value_name = "KEY", conflicts_with = "sortr", help = "Search and report files sorted by KEY, ascending")]
+    pub sort: Option<SortKey>,
+    #[arg(long, value_enum, value_name = "KEY", help = "Search and report files sorted by KEY, descending")]
+    pub sortr: Option<SortKey>,
+    #[arg(long, overrides_with = "no_heading", help = "Print each file path once above its matches (default when stdout is a terminal)")]
+    pub heading: bool,
+    #[arg(long, overrides_with = "heading", help = "Print the file path on every matching line")]
+    pub no_heading: bool,
+    #[arg(long, help = "Flush output after every line, e.g. when following a log with tail -f")]
+    pub line_buffered: bool,
+    #[arg(long, help = "Suppress error messages about unreadable files and directories")]
+    pub no_messages: bool,
+    #[arg(long, help = "Show files and bytes searched so far on stderr, when it is a terminal")]
+    pub progress: bool,
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto, help = "When to use colors")]
+    pub color: ColorChoice,
+    #[arg(
+        long = "colors",
+        value_name = "SPEC",
+        help = "Customize a color, e.g. 'path:fg:magenta' or 'match:bg:yellow' (repeatable; also read from MINIGREP_COLORS)"
+    )]
+    pub colors: Vec<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+#[command(next_help_heading = "Rewrite Options")]
+#[command(group(ArgGroup::new("rewrite_mode").args(["rewrite", "interactive_replace"]).multiple(false)))]
+pub struct RewriteOption {
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "Replace matches in the searched files on disk with TEMPLATE ($1 and ${name} expand capture groups)",
+        conflicts_with_all = &["invert_match", "output_mode_flags", "json"]
+    )]
+    pub rewrite: Option<String>,
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "Like --rewrite, but confirm each edit interactively (context defaults to 2 lines)",
+        conflicts_with_all = &["invert_match", "output_mode_flags", "json"]
+    )]
+    pub interactive_replace: Option<String>,
+    #[arg(long, value_name = "SUFFIX", requires = "rewrite_mode", help = "Keep a copy of each rewritten file with SUFFIX appended to its name")]
+    pub backup: Option<String>,
+    #[arg(long, requires = "rewrite", help = "Print a unified diff of the rewrite instead of writing files")]
+    pub dry_run: bool,
 }
 
 impl Config {
+    /// The pattern; empty only when the search comes from `--request`.
+    pub fn query(&self) -> &str {
+        self.query.as_deref().unwrap_or_default()
+    }
+
+    pub fn use_heading(&self) -> bool {
+        if self.output.heading {
+            true
+        } else if self.output.no_heading {
+            false
+        } else {
+            io::stdout().is_terminal()
+        }
+    }
+
+    /// The order requested with `--sort` or `--sortr`, if any. Without
+    /// one, files are searched in walk order and aggregated output is
+    /// sorted by path.
+    pub fn file_sort(&self) -> Option<FileSort> {
+        match (self.output.sort, self.output.sortr) {
+            (Some(key), _) => Some(FileSort { key, reverse: false }),
+            (None, Some(key)) => Some(FileSort { key, reverse: true }),
+            (None, None) => None,
+        }
+    }
+
+    /// The search itself, as library options.
+    pub fn search_options(&self) -> SearchOptions {
+        let (after_len, before_len) = self.get_effective_context();
+        SearchOptions::new(self.query())
+            .ignore_case(self.search.ignore_case)
+            .invert_match(self.search.invert_match)
+            .only_matching(self.search.only_matching)
+            .before_context(before_len)
+            .after_context(after_len)
+            .sort(self.file_sort())
+    }
+
+    pub fn color_scheme(&self) -> Result<ColorScheme, String> {
+        let env_specs = env::var("MINIGREP_COLORS").ok();
+        ColorScheme::from_specs(env_specs.as_deref(), self.output.colors.iter().map(String::as_str))
+    }
+
     pub fn get_effective_context(&self) -> (usize, usize) {
         if self.output.context > 0 {
             (self.output.context, self.output.context)
@@ -0,0 +1,163 @@
+use std::{error, fmt, io, path::PathBuf};
+
+/// Everything that can go wrong in a search. More variants may be added,
+/// so matches on it need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The pattern failed to compile. `position` is the byte offset of the
+    /// offending syntax, when the problem is a syntax error.
+    Pattern {
+        pattern: String,
+        message: String,
+        position: Option<usize>,
+    },
+    Io { path: PathBuf, source: io::Error },
+    /// A line was not valid UTF-8.
+    Encoding { path: PathBuf, line_number: usize },
+    /// A directory entry could not be read while walking.
+    Walk { path: Option<PathBuf>, message: String },
+    /// The requested combination of options cannot work, e.g. a mode that
+    /// needs a path was given stdin.
+    Unsupported(String),
+    /// An option value was rejected, such as an unknown capture group.
+    Config(String),
+    /// A [`Sink`](crate::Sink) reported an error.
+    Sink(Box<dyn error::Error + Send + Sync>),
+    /// A `--request` document was malformed or asked for something invalid.
+    /// `field` is the path to the offending value, such as `mode.top`, and
+    /// is empty when the document is not valid JSON.
+    Request { field: String, message: String },
+    /// The search's [`CancellationToken`](crate::CancellationToken) was
+    /// cancelled.
+    Cancelled,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io { path: path.into(), source }
+    }
+
+    /// Locates a syntax error by re-parsing the pattern, as `regex::Error`
+    /// only carries a rendered message.
+    pub(crate) fn pattern(pattern: &str, ignore_case: bool, error: regex::Error) -> Self {
+        let parsed = regex_syntax::ParserBuilder::new()
+            .case_insensitive(ignore_case)
+            .build()
+            .parse(pattern);
+        let (message, position) = match parsed {
+            Err(regex_syntax::Error::Parse(e)) => (e.kind().to_string(), Some(e.span().start.offset)),
+            Err(regex_syntax::Error::Translate(e)) => (e.kind().to_string(), Some(e.span().start.offset)),
+            _ => (error.to_string(), None),
+        };
+        Error::Pattern { pattern: pattern.to_string(), message, position }
+    }
+
+    pub(crate) fn walk(error: walkdir::Error) -> Self {
+        let path = error.path().map(|p| p.to_path_buf());
+        let message = match error.io_error() {
+            Some(e) => e.to_string(),
+            None => error.to_string(),
+        };
+        Error::Walk { path, message }
+    }
+
+    /// Whether output stopped because the reader went away, as when piping
+    /// into `head`. The CLI exits quietly in that case.
+    pub fn is_broken_pipe(&self) -> bool {
+        let io_error = match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Sink(e) => e.downcast_ref::<io::Error>(),
+            _ => None,
+        };
+        io_error.is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+    }
+
+    /// Per-file problems that are reported and skipped rather than ending
+    /// the search.
+    pub fn is_per_file(&self) -> bool {
+        matches!(self, Error::Io { .. } | Error::Encoding { .. } | Error::Walk { .. })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Pattern { pattern, message, position: Some(position) } => {
+                write!(f, "invalid pattern '{}' at position {}: {}", pattern, position, message)
+            }
+            Error::Pattern { pattern, message, position: None } => {
+                write!(f, "invalid pattern '{}': {}", pattern, message)
+            }
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Encoding { path, line_number } => {
+                write!(f, "{}: invalid UTF-8 on line {}", path.display(), line_number)
+            }
+            Error::Walk { path: Some(path), message } => {
+                write!(f, "Failed to access {}: {}", path.display(), message)
+            }
+            Error::Walk { path: None, message } => write!(f, "Failed to access path: {}", message),
+            Error::Unsupported(message) | Error::Config(message) => f.write_str(message),
+            Error::Sink(e) => e.fmt(f),
+            Error::Request { field, message } if field.is_empty() => {
+                write!(f, "invalid request: {}", message)
+            }
+            Error::Request { field, message } => write!(f, "invalid request: {}: {}", field, message),
+            Error::Cancelled => f.write_str("search cancelled"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Sink(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Receives non-fatal, per-file problems such as unreadable files. Any
+/// `FnMut(&Error)` closure works as a channel.
+pub trait Diagnostics {
+    fn report(&mut self, error: &Error);
+}
+
+impl<F: FnMut(&Error)> Diagnostics for F {
+    fn report(&mut self, error: &Error) {
+        self(error)
+    }
+}
+
+/// Prints each problem to stderr. The default channel.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StderrDiagnostics;
+
+impl Diagnostics for StderrDiagnostics {
+    fn report(&mut self, error: &Error) {
+        eprintln!("{}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchOptions;
+
+    #[test]
+    fn test_pattern_error_position() {
+        match SearchOptions::new("ab(c").regex().unwrap_err() {
+            Error::Pattern { position, .. } => assert_eq!(position, Some(2)),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_error_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<Error>();
+    }
+}
@@ -0,0 +1,423 @@
+// src/ffi.rs
+
+//! The C API exported by the `cdylib`. Its header, `include/minigrep.h`, is
+//! generated from this file with
+//! `cbindgen --config cbindgen.toml --output include/minigrep.h`.
+//!
+//! Every function catches panics and reports them as
+//! `MINIGREP_STATUS_PANIC`; nothing unwinds into the caller.
+
+use std::{
+    any::Any,
+    error,
+    ffi::{c_char, c_void, CStr, CString},
+    fs,
+    ops::ControlFlow,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr, slice,
+};
+
+use regex::Regex;
+
+use crate::{
+    error::Error,
+    output::{MatchedLine, Sink},
+    search::{control::CancellationToken, options::SearchOptions, Searcher},
+};
+
+/// Result of every fallible call. On failure, the message is available
+/// from `minigrep_search_last_error`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinigrepStatus {
+    Ok = 0,
+    /// A required pointer was null, or a string was not UTF-8.
+    InvalidArgument = 1,
+    /// The pattern does not compile.
+    InvalidPattern = 2,
+    /// The input could not be read.
+    Io = 3,
+    /// Any other failure.
+    Failed = 4,
+    /// The search panicked. The handle may be used again.
+    Panic = 5,
+}
+
+/// A compiled search and its options. Create with `minigrep_search_new`,
+/// release with `minigrep_free`. A handle must not be used from two
+/// threads at once.
+pub struct MinigrepSearch {
+    options: SearchOptions,
+    /// Compiled on first use and kept until an option that affects it
+    /// changes.
+    regex: Option<Regex>,
+    last_error: Option<CString>,
+}
+
+/// One occurrence of the pattern within a matched line.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MinigrepSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A matched line, passed to the match callback. Every pointer is valid
+/// only for the duration of the callback.
+#[repr(C)]
+pub struct MinigrepMatch {
+    /// NUL-terminated path or label of the input.
+    pub path: *const c_char,
+    pub line_number: usize,
+    /// Byte offset of the start of the line within its input.
+    pub byte_offset: u64,
+    /// The line without its terminator: `line_len` bytes of UTF-8, not
+    /// NUL-terminated.
+    pub line: *const c_char,
+    pub line_len: usize,
+    /// Byte ranges of each occurrence within `line`.
+    pub spans: *const MinigrepSpan,
+    pub spans_len: usize,
+}
+
+/// Called for each matched line with the `user_data` given to the search.
+/// Return `true` to continue or `false` to stop the search early, which
+/// still counts as success.
+pub type MinigrepMatchCallback =
+    Option<unsafe extern "C" fn(found: *const MinigrepMatch, user_data: *mut c_void) -> bool>;
+
+type Failure = (MinigrepStatus, String);
+
+impl MinigrepSearch {
+    fn regex(&mut self) -> Result<Regex, Failure> {
+        if let Some(re) = &self.regex {
+            return Ok(re.clone());
+        }
+        let re = self.options.regex().map_err(|e| (MinigrepStatus::InvalidPattern, e.to_string()))?;
+        self.regex = Some(re.clone());
+        Ok(re)
+    }
+
+    fn set_options(&mut self, update: impl FnOnce(SearchOptions) -> SearchOptions) {
+        let options = std::mem::replace(&mut self.options, SearchOptions::new(""));
+        self.options = update(options);
+    }
+
+    /// Runs one search, passing matches to `callback`. Unreadable files
+    /// below a directory are skipped silently.
+    fn run(
+        &mut self,
+        callback: MinigrepMatchCallback,
+        user_data: *mut c_void,
+        search: impl FnOnce(&mut Searcher, &mut CallbackSink) -> crate::Result<()>,
+    ) -> Result<(), Failure> {
+        let Some(callback) = callback else {
+            return Err((MinigrepStatus::InvalidArgument, "callback is null".into()));
+        };
+        let re = self.regex()?;
+        let stop = CancellationToken::new();
+        let mut searcher = Searcher::with_matcher(self.options.matcher(re), self.options.clone())
+            .diagnostics(|_: &Error| {})
+            .cancellation(stop.clone());
+        let mut sink = CallbackSink { callback, user_data, stop, path: CString::default() };
+
+        match search(&mut searcher, &mut sink) {
+            Ok(()) | Err(Error::Cancelled) => Ok(()),
+            Err(e) => Err((status_of(&e), e.to_string())),
+        }
+    }
+}
+
+fn status_of(error: &Error) -> MinigrepStatus {
+    match error {
+        Error::Pattern { .. } => MinigrepStatus::InvalidPattern,
+        Error::Io { .. } | Error::Encoding { .. } | Error::Walk { .. } => MinigrepStatus::Io,
+        _ => MinigrepStatus::Failed,
+    }
+}
+
+struct CallbackSink {
+    callback: unsafe extern "C" fn(*const MinigrepMatch, *mut c_void) -> bool,
+    user_data: *mut c_void,
+    /// Cancelled when the callback asks to stop, ending the walk as well
+    /// as the current file.
+    stop: CancellationToken,
+    path: CString,
+}
+
+impl Sink for CallbackSink {
+    fn begin_file(&mut self, path: &Path) {
+        self.path = CString::new(path.to_string_lossy().into_owned()).unwrap_or_default();
+    }
+
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn error::Error + Send + Sync>> {
+        let spans: Vec<MinigrepSpan> = data.spans
+            .iter()
+            .map(|span| MinigrepSpan { start: span.start, end: span.end })
+            .collect();
+        let found = MinigrepMatch {
+            path: self.path.as_ptr(),
+            line_number: data.line_number,
+            byte_offset: data.byte_offset,
+            line: data.line.as_ptr().cast(),
+            line_len: data.line.len(),
+            spans: spans.as_ptr(),
+            spans_len: spans.len(),
+        };
+
+        // SAFETY: whoever started the search vouched for the callback and
+        // its user data, and `found` outlives the call.
+        if unsafe { (self.callback)(&found, self.user_data) } {
+            Ok(ControlFlow::Continue(()))
+        } else {
+            self.stop.cancel();
+            Ok(ControlFlow::Break(()))
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let detail = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown cause".into());
+    format!("minigrep panicked: {}", detail)
+}
+
+/// Reads a required string argument.
+///
+/// # Safety
+///
+/// `arg` must be null or point to a NUL-terminated string.
+unsafe fn str_arg<'a>(arg: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if arg.is_null() {
+        return Err((MinigrepStatus::InvalidArgument, format!("{} is null", name)));
+    }
+    // SAFETY: non-null, and NUL-terminated per the contract above.
+    unsafe { CStr::from_ptr(arg) }
+        .to_str()
+        .map_err(|_| (MinigrepStatus::InvalidArgument, format!("{} is not valid UTF-8", name)))
+}
+
+/// Runs `f` on the handle behind `search`, recording any failure or panic
+/// as its last error.
+///
+/// # Safety
+///
+/// `search` must be null or a live handle from `minigrep_search_new`.
+unsafe fn with_search(
+    search: *mut MinigrepSearch,
+    f: impl FnOnce(&mut MinigrepSearch) -> Result<(), Failure>,
+) -> MinigrepStatus {
+    // SAFETY: null or a live, unaliased handle per the contract above.
+    let Some(search) = (unsafe { search.as_mut() }) else {
+        return MinigrepStatus::InvalidArgument;
+    };
+    search.last_error = None;
+
+    let (status, message) = match panic::catch_unwind(AssertUnwindSafe(|| f(search))) {
+        Ok(Ok(())) => return MinigrepStatus::Ok,
+        Ok(Err(failure)) => failure,
+        Err(payload) => (MinigrepStatus::Panic, panic_message(payload)),
+    };
+    search.last_error = CString::new(message.replace('\0', " ")).ok();
+    status
+}
+
+/// Creates a search for `pattern`, a NUL-terminated UTF-8 regular
+/// expression. Returns null if `pattern` is null or not UTF-8; a pattern
+/// that does not compile is reported by the first search.
+///
+/// # Safety
+///
+/// `pattern` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn minigrep_search_new(pattern: *const c_char) -> *mut MinigrepSearch {
+    panic::catch_unwind(|| {
+        // SAFETY: forwarded from this function's contract.
+        let Ok(pattern) = (unsafe { str_arg(pattern, "pattern") }) else {
+            return ptr::null_mut();
+        };
+        let search = MinigrepSearch {
+            options: SearchOptions::new(pattern),
+            regex: None,
+            last_error: None,
+        };
+        Box::into_raw(Box::new(search))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// # Safety
+///
+/// `search` must be null or a live handle from `minigrep_search_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn minigrep_search_set_ignore_case(search: *mut MinigrepSearch, yes: bool) -> MinigrepStatus {
+    // SAFETY: forwarded from this function's contract.
+    unsafe {
+        with_search(search, |search| {
+            search.set_options(|options| options.ignore_case(yes));
+            search.regex = None;
+            Ok(())
+        })
+    }
+}
+
+/// # Safety
+///
+/// `search` must be null or a live handle from `minigrep_search_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn minigrep_search_set_invert_match(search: *mut MinigrepSearch, yes: bool) -> MinigrepStatus {
+    // SAFETY: forwarded from this function's contract.
+    unsafe {
+        with_search(search, |search| {
+            search.set_options(|options| options.invert_match(yes));
+            Ok(())
+        })
+    }
+}
+
+/// # Safety
+///
+/// `search` must be null or a live handle from `minigrep_search_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn minigrep_search_set_only_matching(search: *mut MinigrepSearch, yes: bool) -> MinigrepStatus {
+    // SAFETY: forwarded from this function's contract.
+    unsafe {
+        with_search(search, |search| {
+            search.set_options(|options| options.only_matching(yes));
+            Ok(())
+        })
+    }
+}
+
+/// Searches a file, or every searchable file below a directory, calling
+/// `callback` for each matched line.
+///
+/// # Safety
+///
+/// `search` must be null or a live handle from `minigrep_search_new`,
+/// `path` null or a NUL-terminated string, and `callback` must be safe to
+/// call with `user_data`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn minigrep_search_path(
+    search: *mut MinigrepSearch,
+    path: *const c_char,
+    callback: MinigrepMatchCallback,
+    user_data: *mut c_void,
+) -> MinigrepStatus {
+    // SAFETY: forwarded from this function's contract.
+    unsafe {
+        with_search(search, |search| {
+            let path = Path::new(str_arg(path, "path")?);
+            search.run(callback, user_data, |searcher, sink| {
+                fs::metadata(path).map_err(|e| Error::io(path, e))?;
+                searcher.search_path(path, sink)
+            })
+        })
+    }
+}
+
+/// Searches `len` bytes at `data`, reporting matches under `label`.
+///
+/// # Safety
+///
+/// As for `minigrep_search_path`, with `label` in place of `path`; `data`
+/// must point to `len` readable bytes, or may be null when `len` is 0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn minigrep_search_buffer(
+    search: *mut MinigrepSearch,
+    label: *const c_char,
+    data: *const u8,
+    len: usize,
+    callback: MinigrepMatchCallback,
+    user_data: *mut c_void,
+) -> MinigrepStatus {
+    // SAFETY: forwarded from this function's contract.
+    unsafe {
+        with_search(search, |search| {
+            let label = str_arg(label, "label")?;
+            let bytes = match (data.is_null(), len) {
+                (true, 0) => &[][..],
+                (true, _) => return Err((MinigrepStatus::InvalidArgument, "data is null".into())),
+                (false, _) => slice::from_raw_parts(data, len),
+            };
+            search.run(callback, user_data, |searcher, sink| searcher.search_slice(label, bytes, sink))
+        })
+    }
+}
+
+/// The message for the last failed call on `search`, or null if the last
+/// call succeeded. Valid until the next call on the same handle.
+///
+/// # Safety
+///
+/// `search` must be null or a live handle from `minigrep_search_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn minigrep_search_last_error(search: *const MinigrepSearch) -> *const c_char {
+    // SAFETY: forwarded from this function's contract.
+    match unsafe { search.as_ref() }.and_then(|search| search.last_error.as_ref()) {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Releases a handle. Passing null does nothing.
+///
+/// # Safety
+///
+/// `search` must be null or a live handle from `minigrep_search_new`, and
+/// is dangling afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn minigrep_free(search: *mut MinigrepSearch) {
+    if !search.is_null() {
+        // SAFETY: created by `Box::into_raw` in `minigrep_search_new`.
+        let _ = panic::catch_unwind(|| drop(unsafe { Box::from_raw(search) }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn collect(found: *const MinigrepMatch, user_data: *mut c_void) -> bool {
+        let (found, lines) = unsafe { (&*found, &mut *user_data.cast::<Vec<usize>>()) };
+        lines.push(found.line_number);
+        lines.len() < 2
+    }
+
+    #[test]
+    fn test_search_buffer_and_errors() {
+        unsafe {
+            let search = minigrep_search_new(c"todo".as_ptr());
+            assert_eq!(minigrep_search_set_ignore_case(search, true), MinigrepStatus::Ok);
+
+            let input = b"TODO a\nb\ntodo c\ntodo d\n";
+            let mut lines: Vec<usize> = Vec::new();
+            let status = minigrep_search_buffer(
+                search,
+                c"input".as_ptr(),
+                input.as_ptr(),
+                input.len(),
+                Some(collect),
+                (&mut lines as *mut Vec<usize>).cast(),
+            );
+            assert_eq!(status, MinigrepStatus::Ok);
+            assert_eq!(lines, [1, 3]);
+            assert!(minigrep_search_last_error(search).is_null());
+
+            let status = minigrep_search_path(search, ptr::null(), Some(collect), ptr::null_mut());
+            assert_eq!(status, MinigrepStatus::InvalidArgument);
+            let message = CStr::from_ptr(minigrep_search_last_error(search));
+            assert_eq!(message.to_str().unwrap(), "path is null");
+
+            minigrep_free(search);
+        }
+    }
+}
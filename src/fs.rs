@@ -1,10 +1,36 @@
-use std::{fs, io::{self, Read}, path::Path};
+use std::{fs, io::{self, Read}, path::{Path, PathBuf}, time::SystemTime};
 
-pub fn is_hidden(entry: &walkdir::DirEntry) -> bool {
-    entry.file_name()
-         .to_str()
-         .map(|s| s.starts_with('.') || s == "target")
-         .unwrap_or(false)
+use clap::ValueEnum;
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::error::{Diagnostics, Error};
+
+/// Why the walker passed over an entry instead of searching it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Hidden,
+    Ignored,
+    Binary,
+    Unreadable,
+}
+
+pub enum WalkEntry {
+    File(PathBuf),
+    Skipped(SkipReason),
+    /// A hidden or ignored directory, passed over without looking inside,
+    /// so the files in it are not counted.
+    Pruned,
+    /// An entry that could not be read; counted as unreadable.
+    Failed(Error),
+}
+
+pub fn skip_reason(entry: &walkdir::DirEntry) -> Option<SkipReason> {
+    match entry.file_name().to_str() {
+        Some(s) if s.starts_with('.') => Some(SkipReason::Hidden),
+        Some("target") => Some(SkipReason::Ignored),
+        _ => None,
+    }
 }
 
 pub fn is_binary(path: &Path) -> io::Result<bool> {
@@ -12,4 +38,146 @@ pub fn is_binary(path: &Path) -> io::Result<bool> {
     let mut buffer = [0; 1024];
     let n = file.read(&mut buffer)?;
     Ok(buffer[..n].contains(&0))
-}
\ No newline at end of file
+}
+
+/// Walks a path, yielding the files to search and reporting everything it
+/// skips. Hidden and ignored directories are pruned without descending.
+pub struct Walk {
+    inner: walkdir::IntoIter,
+    dirs: Option<Vec<PathBuf>>,
+}
+
+pub fn walk(path: &Path) -> Walk {
+    let mut builder = WalkDir::new(path);
+    if path.is_dir() {
+        builder = builder.min_depth(1);
+    } else {
+        builder = builder.max_depth(0);
+    }
+    Walk { inner: builder.into_iter(), dirs: None }
+}
+
+impl Walk {
+    /// Also remembers each directory descended into, below the root.
+    pub fn record_dirs(mut self) -> Self {
+        self.dirs = Some(Vec::new());
+        self
+    }
+
+    /// The directories seen so far, when recording.
+    pub fn dirs(&self) -> &[PathBuf] {
+        self.dirs.as_deref().unwrap_or_default()
+    }
+}
+
+impl Iterator for Walk {
+    type Item = WalkEntry;
+
+    fn next(&mut self) -> Option<WalkEntry> {
+        loop {
+            let entry = match self.inner.next()? {
+                Ok(e) => e,
+                Err(e) => return Some(WalkEntry::Failed(Error::walk(e))),
+            };
+
+            if let Some(reason) = skip_reason(&entry) {
+                if entry.file_type().is_dir() {
+                    self.inner.skip_current_dir();
+                    return Some(WalkEntry::Pruned);
+                }
+                return Some(WalkEntry::Skipped(reason));
+            }
+
+            if entry.file_type().is_dir() {
+                if let Some(dirs) = self.dirs.as_mut() {
+                    dirs.push(entry.path().to_path_buf());
+                }
+                continue;
+            }
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let path = entry.into_path();
+            return Some(match is_binary(&path) {
+                Ok(false) => WalkEntry::File(path),
+                Ok(true) => WalkEntry::Skipped(SkipReason::Binary),
+                Err(e) => WalkEntry::Failed(Error::io(path, e)),
+            });
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Path,
+    Modified,
+    Accessed,
+    Created,
+    None,
+}
+
+/// Order in which files are searched and reported, from `--sort`/`--sortr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSort {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl FileSort {
+    /// Sorts `files` in place. Files whose timestamp cannot be read sort
+    /// before all others, as if they were the oldest.
+    pub fn sort(self, files: &mut [PathBuf]) {
+        let timestamp = |path: &PathBuf, read: fn(&fs::Metadata) -> io::Result<SystemTime>| {
+            fs::metadata(path).and_then(|m| read(&m)).ok()
+        };
+
+        match self.key {
+            SortKey::None => return,
+            SortKey::Path => files.sort(),
+            SortKey::Modified => files.sort_by_cached_key(|p| timestamp(p, fs::Metadata::modified)),
+            SortKey::Accessed => files.sort_by_cached_key(|p| timestamp(p, fs::Metadata::accessed)),
+            SortKey::Created => files.sort_by_cached_key(|p| timestamp(p, fs::Metadata::created)),
+        }
+        if self.reverse {
+            files.reverse();
+        }
+    }
+}
+
+/// Like [`walk`], but yields files in `sort` order. Sorting needs every
+/// path up front, so the walk is collected first; skipped entries come
+/// before the files.
+pub fn sorted_walk(path: &Path, sort: Option<FileSort>) -> Box<dyn Iterator<Item = WalkEntry>> {
+    let Some(sort) = sort.filter(|s| s.key != SortKey::None) else {
+        return Box::new(walk(path));
+    };
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in walk(path) {
+        match entry {
+            WalkEntry::File(file) => files.push(file),
+            skip => skipped.push(skip),
+        }
+    }
+    sort.sort(&mut files);
+    Box::new(skipped.into_iter().chain(files.into_iter().map(WalkEntry::File)))
+}
+
+/// Walks `path` and yields every file that should be searched, skipping
+/// hidden entries and binary files. Unreadable entries go to `diagnostics`.
+pub fn searchable_files<'d>(
+    path: &Path,
+    diagnostics: &'d mut dyn Diagnostics,
+) -> impl Iterator<Item = PathBuf> + 'd {
+    walk(path).filter_map(move |entry| match entry {
+        WalkEntry::File(path) => Some(path),
+        WalkEntry::Skipped(_) | WalkEntry::Pruned => None,
+        WalkEntry::Failed(e) => {
+            diagnostics.report(&e);
+            None
+        }
+    })
+}
@@ -1,23 +1,55 @@
+//! A simple grep clone. The `minigrep` binary is a thin layer over this
+//! library: build [`SearchOptions`], hand them to a [`Searcher`], and
+//! receive results through your own [`Sink`].
 mod config;
+mod error;
 mod matcher;
 mod output;
 mod app;
 mod search;
 mod fs;
+mod rewrite;
+mod vfs;
+mod request;
+mod serve;
+mod ffi;
 pub use config::Config;
-use crate::{app::App, config::OutputMode}; 
-use regex::RegexBuilder;
-use std::error::Error;
+pub use error::{Diagnostics, Error, Result, StderrDiagnostics};
+pub use fs::{FileSort, SortKey};
+pub use matcher::{impls::{DefaultMatcher, OnlyMatchingMatcher}, MatchResult, Matcher};
+pub use output::{ContextKind, ContextLine, MatchedLine, Sink};
+pub use search::{control::{CancellationToken, Progress, ProgressListener}, matches::{Match, Matches}, options::SearchOptions, stats::{SearchStats, SkippedFiles}, Searcher};
+pub use vfs::{MemoryFs, Vfs};
+#[cfg(feature = "async")]
+pub use search::stream::{search_async, AsyncMatches};
+use std::path::PathBuf;
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let re = RegexBuilder::new(&config.query)
-        .case_insensitive(config.search.ignore_case)
-        .build()?;
+use crate::{app::App, config::OutputMode, request::Request}; 
+
+pub fn run(config: Config) -> Result<()> {
+    if config.serve {
+        return serve::run();
+    }
+    if let Some(source) = &config.request {
+        let request = Request::read(source)?;
+        request.compile()?;
+        let paths = request.paths();
+        return search(&request.into_config()?, paths);
+    }
+
+    let paths = config.path.iter().map(PathBuf::from).collect();
+    search(&config, paths)
+}
+
+fn search(config: &Config, paths: Vec<PathBuf>) -> Result<()> {
+    let options = config.search_options();
+    let re = options.regex()?;
     
-    let output_mode = OutputMode::try_from(&config.mode_args)?;
+    let output_mode = OutputMode::try_from(&config.mode_args).map_err(|e| Error::Config(e.to_string()))?;
+    colored::control::set_override(config.output.color.should_colorize(output_mode));
+    let colors = config.color_scheme().map_err(Error::Config)?;
 
-    let app = App::new(&config, &re, output_mode);
+    let app = App::new(config, paths, options, &re, output_mode, colors);
     
     app.execute()
 }
-
@@ -4,6 +4,9 @@ use minigrep::Config;
 
 fn main() {
     if let Err(e) = minigrep::run(Config::parse()) {
+        if e.is_broken_pipe() {
+            process::exit(0);
+        }
         eprintln!("Application error: {e}");
         process::exit(1);
     }
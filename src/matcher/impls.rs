@@ -1,13 +1,23 @@
+use std::ops::Range;
+
 use regex::Regex;
 
 use crate::matcher::{MatchResult, Matcher};
 
-pub(crate) struct DefaultMatcher<'a> {
-    pub(crate) re: &'a Regex,
-    pub(crate) invert_match: bool,
+/// Matches whole lines containing the pattern, or lacking it when inverted.
+#[derive(Debug, Clone)]
+pub struct DefaultMatcher {
+    re: Regex,
+    invert_match: bool,
+}
+
+impl DefaultMatcher {
+    pub fn new(re: Regex, invert_match: bool) -> Self {
+        Self { re, invert_match }
+    }
 }
 
-impl<'a> Matcher for DefaultMatcher<'a> {
+impl Matcher for DefaultMatcher {
     fn find<'b>(&self, line: &'b str) -> Option<MatchResult<'b>> {
         let is_match = self.re.is_match(line);
         if (is_match && !self.invert_match) || (!is_match && self.invert_match) {
@@ -16,13 +26,25 @@ impl<'a> Matcher for DefaultMatcher<'a> {
             None
         }
     }
+
+    fn spans(&self, line: &str) -> Vec<Range<usize>> {
+        self.re.find_iter(line).map(|m| m.range()).collect()
+    }
 }
 
-pub(crate) struct OnlyMatchingMatcher<'a> {
-    pub(crate) re: &'a Regex,
+/// Matches lines containing the pattern and reports each occurrence.
+#[derive(Debug, Clone)]
+pub struct OnlyMatchingMatcher {
+    re: Regex,
 }
 
-impl<'a> Matcher for OnlyMatchingMatcher<'a> {
+impl OnlyMatchingMatcher {
+    pub fn new(re: Regex) -> Self {
+        Self { re }
+    }
+}
+
+impl Matcher for OnlyMatchingMatcher {
     fn find<'b>(&self, line: &'b str) -> Option<MatchResult<'b>> {
         let matches: Vec<&str> = self.re.find_iter(line).map(|m| m.as_str()).collect();
         if matches.is_empty() {
@@ -31,6 +53,10 @@ impl<'a> Matcher for OnlyMatchingMatcher<'a> {
             Some(MatchResult::Content(matches))
         }
     }
+
+    fn spans(&self, line: &str) -> Vec<Range<usize>> {
+        self.re.find_iter(line).map(|m| m.range()).collect()
+    }
 }
 
 #[cfg(test)]
@@ -41,7 +67,7 @@ mod tests {
     #[test]
     fn test_default_matcher() {
         let re = Regex::new("test").unwrap();
-        let matcher = DefaultMatcher { re: &re, invert_match: false };
+        let matcher = DefaultMatcher::new(re, false);
         let line = "this is a test line";
         if let Some(MatchResult::Line(content)) = matcher.find(line) {
             assert_eq!(content, "this is a test line");
@@ -55,7 +81,7 @@ mod tests {
     #[test]
     fn test_default_matcher_invert() {
         let re = Regex::new("test").unwrap();
-        let matcher = DefaultMatcher { re: &re, invert_match: true };
+        let matcher = DefaultMatcher::new(re, true);
 
         let line_no_match = "no match here";
         if let Some(MatchResult::Line(content)) = matcher.find(line_no_match) {
@@ -71,7 +97,7 @@ mod tests {
     #[test]
     fn test_only_matching_matcher() {
         let re = Regex::new(r"\d+").unwrap();
-        let matcher = OnlyMatchingMatcher { re: &re };
+        let matcher = OnlyMatchingMatcher::new(re);
         
         let line = "hello 123 world 456";
         if let Some(MatchResult::Content(matches)) = matcher.find(line) {
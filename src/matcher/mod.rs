@@ -1,9 +1,18 @@
 pub mod impls;
 
+use std::ops::Range;
+
+/// Decides whether a line matches. Implement this to plug a custom
+/// matching strategy into a [`Searcher`](crate::Searcher).
 pub trait Matcher {
+    /// Returns what matched in `line`, or `None` when the line does not match.
     fn find<'a>(&self, line: &'a str) -> Option<MatchResult<'a>>;
+
+    /// Byte ranges of every occurrence of the pattern in `line`.
+    fn spans(&self, line: &str) -> Vec<Range<usize>>;
 }
 
+/// What a [`Matcher`] reports for a matching line.
 #[derive(Debug, PartialEq)]
 pub enum MatchResult<'a> {
     Content(Vec<&'a str>),
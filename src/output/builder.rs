@@ -0,0 +1,131 @@
+use std::{fs::File, io::{self, BufWriter, IsTerminal, Write}, path::PathBuf};
+
+use regex::Regex;
+
+use crate::{
+    config::{Config, OutputMode},
+    error::{Error, Result},
+    output::{
+        colors::ColorScheme,
+        delimited::Delimiter,
+        sinks::{CountSink, DelimitedSink, FilesWithMatchesSink, FilesWithoutMatchSink, GroupBySink, HtmlReportSink, JsonSink, SarifSink, StandardSink, TallySink, TeeSink, TemplateSink},
+        tally,
+        template::Template,
+        Sink,
+    },
+};
+
+/// Builds the CLI's sink for an output mode, teeing in any extra outputs.
+pub(crate) struct SinkBuilder<'a> {
+    config: &'a Config,
+    re: &'a Regex,
+    colors: &'a ColorScheme,
+}
+
+impl<'a> SinkBuilder<'a> {
+    pub(crate) fn new(config: &'a Config, re: &'a Regex, colors: &'a ColorScheme) -> Self {
+        Self { config, re, colors }
+    }
+
+    pub(crate) fn build(
+        &self,
+        mode: OutputMode,
+        all_files: Option<Vec<PathBuf>>,
+    ) -> Result<Box<dyn Sink + 'a>> {
+        let sink: Box<dyn Sink + 'a> = match mode {
+            OutputMode::Standard => Box::new(StandardSink::new(self.config, self.re, self.colors, self.stdout())),
+            OutputMode::Json => Box::new(JsonSink::new(self.stdout())),
+            OutputMode::Count => Box::new(CountSink::new(self.config, self.colors, self.stdout())),
+            OutputMode::FilesWithMatches => Box::new(FilesWithMatchesSink::new(self.config, self.colors, self.stdout())),
+            OutputMode::FilesWithoutMatch => {
+                let files = all_files.expect("List of all files is required for --files-without-match");
+                Box::new(FilesWithoutMatchSink::new(files, self.colors, self.stdout()))
+            }
+            OutputMode::Sarif => Box::new(self.sarif_sink(self.stdout())),
+            OutputMode::Csv => Box::new(DelimitedSink::new(self.re, Delimiter::Comma, self.stdout())),
+            OutputMode::Tsv => Box::new(DelimitedSink::new(self.re, Delimiter::Tab, self.stdout())),
+            OutputMode::Tally => {
+                let group = match &self.config.mode_args.tally_group {
+                    Some(name) => tally::resolve_group(self.re, name, "--tally-group").map_err(Error::Config)?,
+                    None => 0,
+                };
+                Box::new(TallySink::new(self.config, self.re, group, self.colors, self.stdout()))
+            }
+            OutputMode::GroupBy => {
+                let mode_args = &self.config.mode_args;
+                let keys = mode_args.group_by
+                    .iter()
+                    .map(|name| tally::resolve_group(self.re, name, "--group-by"))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(Error::Config)?;
+                let sum = mode_args.sum
+                    .as_deref()
+                    .map(|name| tally::resolve_group(self.re, name, "--sum"))
+                    .transpose()
+                    .map_err(Error::Config)?;
+                Box::new(GroupBySink::new(self.config, self.re, keys, sum, self.stdout()))
+            }
+            OutputMode::Template => {
+                let format = self.config.mode_args.format.as_deref().unwrap_or_default();
+                let template = Template::parse(format, self.re).map_err(Error::Config)?;
+                let context_template = self.config.output.context_format
+                    .as_deref()
+                    .map(|f| Template::parse_context(f, self.re))
+                    .transpose()
+                    .map_err(Error::Config)?;
+                Box::new(TemplateSink::new(self.re, self.config.query(), template, context_template, self.stdout()))
+            }
+        };
+
+        let extra_sinks = self.build_extra_sinks()?;
+        if extra_sinks.is_empty() {
+            return Ok(sink);
+        }
+
+        let mut sinks = vec![sink];
+        sinks.extend(extra_sinks);
+        Ok(Box::new(TeeSink::new(sinks)))
+    }
+
+    /// Sinks requested with `--json-out`, `--sarif-out`, `--csv-out` and
+    /// `--html-report`, which run alongside the primary output.
+    fn build_extra_sinks(&self) -> Result<Vec<Box<dyn Sink + 'a>>> {
+        let output = &self.config.output;
+        let mut sinks: Vec<Box<dyn Sink + 'a>> = Vec::new();
+
+        if let Some(path) = &output.json_out {
+            sinks.push(Box::new(JsonSink::new(create_output(path)?)));
+        }
+        if let Some(path) = &output.sarif_out {
+            sinks.push(Box::new(self.sarif_sink(create_output(path)?)));
+        }
+        if let Some(path) = &output.csv_out {
+            sinks.push(Box::new(DelimitedSink::new(self.re, Delimiter::Comma, create_output(path)?)));
+        }
+        if let Some(path) = &output.html_report {
+            sinks.push(Box::new(HtmlReportSink::new(self.re, self.config.query(), create_output(path)?)));
+        }
+
+        Ok(sinks)
+    }
+
+    /// Standard output, flushed after every line with `--line-buffered` or
+    /// when writing to a terminal, and block buffered otherwise.
+    fn stdout(&self) -> Box<dyn Write> {
+        if self.config.output.line_buffered || io::stdout().is_terminal() {
+            Box::new(io::stdout())
+        } else {
+            Box::new(BufWriter::new(io::stdout()))
+        }
+    }
+
+    fn sarif_sink(&self, out: Box<dyn Write>) -> SarifSink<'a, Box<dyn Write>> {
+        SarifSink::new(self.re, self.config.query(), self.config.output.sarif_level, out)
+    }
+}
+
+fn create_output(path: &str) -> Result<Box<dyn Write>> {
+    let file = File::create(path).map_err(|e| Error::io(path, e))?;
+    Ok(Box::new(BufWriter::new(file)))
+}
+
@@ -0,0 +1,261 @@
+use colored::*;
+
+/// A color from a spec. Palette indexes above 15 and RGB values are kept
+/// apart from `colored`'s truecolor, which it downgrades to the basic 16
+/// colors unless `COLORTERM` says truecolor is supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecColor {
+    Color(Color),
+    /// An index into the xterm 256-color palette.
+    Palette(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl SpecColor {
+    /// The SGR parameters selecting this color, `base` being 38 for the
+    /// foreground and 48 for the background.
+    fn sgr(self, base: u8) -> String {
+        match self {
+            SpecColor::Color(color) if base == 38 => color.to_fg_str().into_owned(),
+            SpecColor::Color(color) => color.to_bg_str().into_owned(),
+            SpecColor::Palette(index) => format!("{};5;{}", base, index),
+            SpecColor::Rgb(r, g, b) => format!("{};2;{};{};{}", base, r, g, b),
+        }
+    }
+}
+
+impl From<Color> for SpecColor {
+    fn from(color: Color) -> Self {
+        SpecColor::Color(color)
+    }
+}
+
+/// How one kind of output element is painted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColorStyle {
+    pub fg: Option<SpecColor>,
+    pub bg: Option<SpecColor>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub dimmed: bool,
+}
+
+impl ColorStyle {
+    fn fg(color: Color) -> Self {
+        Self { fg: Some(color.into()), ..Self::default() }
+    }
+
+    pub fn paint(&self, text: &str) -> String {
+        let sgr = self.sgr();
+        if sgr.is_empty() || !colored::control::SHOULD_COLORIZE.should_colorize() {
+            text.to_string()
+        } else {
+            format!("\x1b[{}m{}\x1b[0m", sgr, text)
+        }
+    }
+
+    /// The SGR parameters for this style, written out here rather than by
+    /// `colored` so extended colors reach the terminal as given.
+    fn sgr(&self) -> String {
+        let styles = [(self.bold, "1"), (self.dimmed, "2"), (self.italic, "3"), (self.underline, "4")];
+        let mut codes: Vec<String> = styles
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, code)| code.to_string())
+            .collect();
+        codes.extend(self.fg.map(|fg| fg.sgr(38)));
+        codes.extend(self.bg.map(|bg| bg.sgr(48)));
+        codes.join(";")
+    }
+}
+
+/// The palette used by the terminal sinks. Specs look like
+/// `path:fg:magenta`, `match:bg:yellow`, `line:style:bold` or `column:none`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorScheme {
+    pub path: ColorStyle,
+    pub line: ColorStyle,
+    pub column: ColorStyle,
+    pub matched: ColorStyle,
+    pub context: ColorStyle,
+    pub separator: ColorStyle,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self {
+            path: ColorStyle::fg(Color::Cyan),
+            line: ColorStyle::fg(Color::Green),
+            column: ColorStyle::default(),
+            matched: ColorStyle { bold: true, ..ColorStyle::fg(Color::Red) },
+            context: ColorStyle::default(),
+            separator: ColorStyle::default(),
+        }
+    }
+}
+
+impl ColorScheme {
+    /// Builds the scheme from `MINIGREP_COLORS` (whitespace or `;`
+    /// separated) followed by the command-line specs, so flags win.
+    pub fn from_specs<'a>(
+        env_specs: Option<&'a str>,
+        specs: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self, String> {
+        let mut scheme = Self::default();
+        let env_specs = env_specs
+            .into_iter()
+            .flat_map(|s| s.split(|c: char| c == ';' || c.is_whitespace()))
+            .filter(|s| !s.is_empty());
+
+        for spec in env_specs.chain(specs) {
+            scheme.apply(spec)?;
+        }
+        Ok(scheme)
+    }
+
+    fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let invalid = |reason: &str| format!("invalid color spec '{}': {}", spec, reason);
+        let mut parts = spec.splitn(3, ':');
+        let target = parts.next().unwrap_or_default();
+        let attribute = parts.next().ok_or_else(|| invalid("expected TYPE:ATTRIBUTE[:VALUE]"))?;
+        let value = parts.next();
+
+        let style = self.style_mut(target).ok_or_else(|| {
+            invalid("type must be one of path, line, column, match, context, separator")
+        })?;
+
+        match (attribute, value) {
+            ("none", None) => *style = ColorStyle::default(),
+            ("fg", Some(value)) => style.fg = Some(parse_color(value).map_err(|e| invalid(&e))?),
+            ("bg", Some(value)) => style.bg = Some(parse_color(value).map_err(|e| invalid(&e))?),
+            ("style", Some("bold")) => style.bold = true,
+            ("style", Some("nobold")) => style.bold = false,
+            ("style", Some("italic")) => style.italic = true,
+            ("style", Some("noitalic")) => style.italic = false,
+            ("style", Some("underline")) => style.underline = true,
+            ("style", Some("nounderline")) => style.underline = false,
+            ("style", Some("dimmed")) => style.dimmed = true,
+            ("style", Some("nodimmed")) => style.dimmed = false,
+            ("style", _) => return Err(invalid("unknown style")),
+            _ => return Err(invalid("attribute must be fg, bg, style or none")),
+        }
+        Ok(())
+    }
+
+    fn style_mut(&mut self, target: &str) -> Option<&mut ColorStyle> {
+        match target {
+            "path" => Some(&mut self.path),
+            "line" => Some(&mut self.line),
+            "column" => Some(&mut self.column),
+            "match" => Some(&mut self.matched),
+            "context" => Some(&mut self.context),
+            "separator" => Some(&mut self.separator),
+            _ => None,
+        }
+    }
+}
+
+/// Accepts color names, 256-color palette indices (`0`-`255`) and truecolor
+/// values written as `#rrggbb` or `r,g,b`.
+fn parse_color(value: &str) -> Result<SpecColor, String> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| format!("bad hex color '{}'", value))
+        };
+        if hex.len() != 6 {
+            return Err(format!("bad hex color '{}'", value));
+        }
+        return Ok(SpecColor::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+
+    if value.contains(',') {
+        let channels: Vec<u8> = value
+            .split(',')
+            .map(|c| c.trim().parse::<u8>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("bad RGB color '{}'", value))?;
+        return match channels[..] {
+            [r, g, b] => Ok(SpecColor::Rgb(r, g, b)),
+            _ => Err(format!("bad RGB color '{}'", value)),
+        };
+    }
+
+    if let Ok(index) = value.parse::<u8>() {
+        return Ok(ansi_256(index));
+    }
+
+    value
+        .replace('_', " ")
+        .parse::<Color>()
+        .map(SpecColor::Color)
+        .map_err(|_| format!("unknown color '{}'", value))
+}
+
+/// Maps the 16 basic palette indexes onto their `colored` names, and keeps
+/// the rest as indexes.
+fn ansi_256(index: u8) -> SpecColor {
+    const BASIC: [Color; 16] = [
+        Color::Black, Color::Red, Color::Green, Color::Yellow,
+        Color::Blue, Color::Magenta, Color::Cyan, Color::White,
+        Color::BrightBlack, Color::BrightRed, Color::BrightGreen, Color::BrightYellow,
+        Color::BrightBlue, Color::BrightMagenta, Color::BrightCyan, Color::BrightWhite,
+    ];
+
+    match BASIC.get(index as usize) {
+        Some(&color) => color.into(),
+        None => SpecColor::Palette(index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_specs_override_defaults() {
+        let scheme = ColorScheme::from_specs(
+            Some("path:fg:magenta;line:none"),
+            ["match:bg:yellow", "match:style:nobold"],
+        )
+        .unwrap();
+
+        assert_eq!(scheme.path.fg, Some(Color::Magenta.into()));
+        assert_eq!(scheme.line, ColorStyle::default());
+        assert_eq!(scheme.matched.fg, Some(Color::Red.into()));
+        assert_eq!(scheme.matched.bg, Some(Color::Yellow.into()));
+        assert!(!scheme.matched.bold);
+    }
+
+    #[test]
+    fn test_extended_colors() {
+        assert_eq!(parse_color("#ff8000"), Ok(SpecColor::Rgb(255, 128, 0)));
+        assert_eq!(parse_color("1,2,3"), Ok(SpecColor::Rgb(1, 2, 3)));
+        assert_eq!(parse_color("9"), Ok(Color::BrightRed.into()));
+        assert_eq!(parse_color("208"), Ok(SpecColor::Palette(208)));
+        assert_eq!(parse_color("232"), Ok(SpecColor::Palette(232)));
+        assert!(parse_color("#12").is_err());
+        assert!(parse_color("chartreuse").is_err());
+    }
+
+    #[test]
+    fn test_unknown_targets_are_rejected() {
+        assert!(ColorScheme::from_specs(None, ["bogus:fg:red"]).is_err());
+        assert!(ColorScheme::from_specs(None, ["match.1:fg:blue"]).is_err());
+    }
+
+    #[test]
+    fn test_extended_colors_use_their_own_codes() {
+        let style = ColorStyle { bg: Some(SpecColor::Palette(17)), ..ColorStyle::fg(Color::Red) };
+        assert_eq!(style.sgr(), "31;48;5;17");
+        let style = ColorStyle { fg: Some(SpecColor::Palette(208)), bold: true, ..ColorStyle::default() };
+        assert_eq!(style.sgr(), "1;38;5;208");
+
+        let scheme = ColorScheme::from_specs(None, ["line:bg:#ff0000", "path:fg:0,128,255"]).unwrap();
+        assert_eq!(scheme.line.sgr(), "32;48;2;255;0;0");
+        assert_eq!(scheme.path.sgr(), "38;2;0;128;255");
+        assert_eq!(ColorStyle::default().sgr(), "");
+    }
+}
@@ -0,0 +1,68 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+}
+
+impl Delimiter {
+    /// Joins `fields` into a single record, including its terminator.
+    pub(crate) fn record<'a>(self, fields: impl IntoIterator<Item = &'a str>) -> String {
+        let (separator, terminator) = match self {
+            Delimiter::Comma => (",", "\r\n"),
+            Delimiter::Tab => ("\t", "\n"),
+        };
+        let encoded: Vec<String> = fields.into_iter().map(|f| self.encode(f)).collect();
+        let mut record = encoded.join(separator);
+        record.push_str(terminator);
+        record
+    }
+
+    fn encode(self, field: &str) -> String {
+        match self {
+            Delimiter::Comma => quote_csv(field),
+            Delimiter::Tab => escape_tsv(field),
+        }
+    }
+}
+
+/// Quotes a field as described by RFC 4180: fields containing commas,
+/// quotes or line breaks are wrapped in quotes, with quotes doubled.
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV cannot quote, so tabs, line breaks and backslashes are escaped.
+fn escape_tsv(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_quoting() {
+        let record = Delimiter::Comma.record(["plain", "a,b", "say \"hi\"", "two\nlines", ""]);
+        assert_eq!(record, "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\r\n");
+    }
+
+    #[test]
+    fn test_tsv_escaping() {
+        let record = Delimiter::Tab.record(["a\tb", "c\\d", "e\r\nf", "g,\"h\""]);
+        assert_eq!(record, "a\\tb\tc\\\\d\te\\r\\nf\tg,\"h\"\n");
+    }
+}
@@ -1,14 +1,29 @@
 use std::path::Path;
-use colored::*;
-use crate::{output::ContextKind, Config};
+use crate::{output::{colors::ColorScheme, ContextKind}, Config};
 
 pub struct OutputFormatter<'a> {
     config: &'a Config,
+    pub(crate) colors: &'a ColorScheme,
+    heading: bool,
 }
 
 impl<'a> OutputFormatter<'a> {
-    pub fn new(config: &'a Config) -> Self {
-        Self { config }
+    pub fn new(config: &'a Config, colors: &'a ColorScheme) -> Self {
+        Self { config, colors, heading: config.use_heading() }
+    }
+
+    fn is_multi_file_context(&self) -> bool {
+        self.config.path.as_ref().is_some_and(|p| Path::new(p).is_dir())
+    }
+
+    /// Whether file paths are printed once above their matches instead of
+    /// on every line.
+    pub(crate) fn uses_heading(&self) -> bool {
+        self.heading && self.is_multi_file_context()
+    }
+
+    pub(crate) fn format_heading(&self, file_path: &Path) -> String {
+        self.colors.path.paint(&file_path.display().to_string()).to_string()
     }
 
     pub(crate) fn format_prefix(
@@ -18,17 +33,23 @@ impl<'a> OutputFormatter<'a> {
         context_kind: Option<ContextKind> 
     ) -> String {
         let mut prefix = String::new();
-        let is_multi_file_context = self.config.path.as_ref().map_or(false, |p| Path::new(p).is_dir());
-
-        if is_multi_file_context {
-            prefix.push_str(&format!("{}:", file_path.display().to_string().cyan()));
+        if self.is_multi_file_context() && !self.heading {
+            prefix.push_str(&format!(
+                "{}{}",
+                self.colors.path.paint(&file_path.display().to_string()),
+                self.colors.separator.paint(":")
+            ));
         }
         
         let separator = match context_kind {
             Some(_) => "-",
             None => ":",
         };
-        prefix.push_str(&format!("{}{}", line_number.to_string().green(), separator));
+        prefix.push_str(&format!(
+            "{}{}",
+            self.colors.line.paint(&line_number.to_string()),
+            self.colors.separator.paint(separator)
+        ));
 
         prefix
     }
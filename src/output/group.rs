@@ -0,0 +1,209 @@
+use std::{collections::HashMap, fmt};
+
+use regex::Captures;
+use serde::{Serialize, Serializer};
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct GroupRow {
+    pub(crate) count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) sum: Option<Sum>,
+    /// `--sum` captures that were not numbers and were left out of `sum`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) non_numeric: Option<u64>,
+}
+
+/// A `--sum` total. It stays an exact integer until a value with a
+/// fraction or exponent turns up, or the integer would overflow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Sum {
+    Int(i128),
+    Float(f64),
+}
+
+impl Sum {
+    /// Adds `text`, or returns `None` if it is not a finite number.
+    fn add(self, text: &str) -> Option<Sum> {
+        if let Ok(n) = text.parse::<i128>() {
+            return Some(match self {
+                Sum::Int(total) => total.checked_add(n).map_or(Sum::Float(total as f64 + n as f64), Sum::Int),
+                Sum::Float(total) => Sum::Float(total + n as f64),
+            });
+        }
+        let x = text.parse::<f64>().ok().filter(|x| x.is_finite())?;
+        Some(Sum::Float(self.as_f64() + x))
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Sum::Int(n) => n as f64,
+            Sum::Float(x) => x,
+        }
+    }
+}
+
+impl fmt::Display for Sum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sum::Int(n) => n.fmt(f),
+            Sum::Float(x) => x.fmt(f),
+        }
+    }
+}
+
+impl Serialize for Sum {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Sum::Int(n) => serializer.serialize_i128(n),
+            Sum::Float(x) => serializer.serialize_f64(x),
+        }
+    }
+}
+
+/// Match counts (and optional sums) keyed by the tuple of `--group-by`
+/// captures. Groups that did not participate in a match key as `None`.
+#[derive(Debug)]
+pub(crate) struct GroupTable {
+    keys: Vec<usize>,
+    sum: Option<usize>,
+    rows: HashMap<Vec<Option<String>>, GroupRow>,
+}
+
+impl GroupTable {
+    pub(crate) fn new(keys: Vec<usize>, sum: Option<usize>) -> Self {
+        Self { keys, sum, rows: HashMap::new() }
+    }
+
+    pub(crate) fn add(&mut self, caps: &Captures<'_>) {
+        let key = self.keys
+            .iter()
+            .map(|&i| caps.get(i).map(|m| m.as_str().to_string()))
+            .collect();
+        let row = self.rows.entry(key).or_default();
+        row.count += 1;
+
+        if let Some(index) = self.sum {
+            let sum = row.sum.get_or_insert(Sum::Int(0));
+            let non_numeric = row.non_numeric.get_or_insert(0);
+            // A group that took no part in the match has nothing to add.
+            if let Some(m) = caps.get(index) {
+                match sum.add(m.as_str()) {
+                    Some(total) => *sum = total,
+                    None => *non_numeric += 1,
+                }
+            }
+        }
+    }
+
+    /// Rows ordered by key.
+    pub(crate) fn rows(&self) -> Vec<(&[Option<String>], &GroupRow)> {
+        let mut rows: Vec<_> = self.rows.iter().map(|(k, r)| (k.as_slice(), r)).collect();
+        rows.sort_by(|a, b| a.0.cmp(b.0));
+        rows
+    }
+
+    /// Renders an aligned table with one column per key, then the count and
+    /// the sum, and a count of values left out of the sum if there were
+    /// any. Text columns are left aligned and numbers right aligned.
+    pub(crate) fn render_table(&self, key_names: &[String], sum_name: Option<&str>) -> String {
+        let mut header: Vec<String> = key_names.to_vec();
+        header.push("count".to_string());
+        if let Some(name) = sum_name {
+            header.push(format!("sum({})", name));
+        }
+        let show_non_numeric = self.rows.values().any(|row| row.non_numeric.unwrap_or(0) > 0);
+        if show_non_numeric {
+            header.push("non-numeric".to_string());
+        }
+
+        let mut lines = vec![header];
+        for (key, row) in self.rows() {
+            let mut cells: Vec<String> = key.iter().map(|k| k.as_deref().unwrap_or("-").to_string()).collect();
+            cells.push(row.count.to_string());
+            if let Some(sum) = row.sum {
+                cells.push(sum.to_string());
+            }
+            if show_non_numeric {
+                cells.push(row.non_numeric.unwrap_or(0).to_string());
+            }
+            lines.push(cells);
+        }
+
+        let widths: Vec<usize> = (0..lines[0].len())
+            .map(|col| lines.iter().map(|cells| cells[col].chars().count()).max().unwrap_or(0))
+            .collect();
+
+        let mut out = String::new();
+        for cells in &lines {
+            let padded: Vec<String> = cells
+                .iter()
+                .enumerate()
+                .map(|(col, cell)| {
+                    if col < self.keys.len() {
+                        format!("{:<width$}", cell, width = widths[col])
+                    } else {
+                        format!("{:>width$}", cell, width = widths[col])
+                    }
+                })
+                .collect();
+            out.push_str(padded.join("  ").trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn test_group_and_sum() {
+        let re = Regex::new(r"(?P<user>\w+) (?P<status>\d+) (?P<bytes>\d+)").unwrap();
+        let mut table = GroupTable::new(vec![1, 2], Some(3));
+        for line in ["bob 200 10", "alice 404 5", "bob 200 32", "alice 200 7"] {
+            table.add(&re.captures(line).unwrap());
+        }
+
+        let names = ["user".to_string(), "status".to_string()];
+        assert_eq!(
+            table.render_table(&names, Some("bytes")),
+            "user   status  count  sum(bytes)\n\
+             alice  200         1           7\n\
+             alice  404         1           5\n\
+             bob    200         2          42\n"
+        );
+    }
+
+    #[test]
+    fn test_sum_is_exact_for_integers() {
+        let re = Regex::new(r"(\w+)=(\S+)").unwrap();
+        let mut table = GroupTable::new(vec![1], Some(2));
+        for line in ["a=10", "a=9007199254740993", "b=1.5", "b=2"] {
+            table.add(&re.captures(line).unwrap());
+        }
+
+        let rows = table.rows();
+        assert_eq!(rows[0].1.sum, Some(Sum::Int(9007199254741003)));
+        assert_eq!(rows[1].1.sum, Some(Sum::Float(3.5)));
+        assert_eq!(serde_json::to_string(rows[0].1).unwrap(), r#"{"count":2,"sum":9007199254741003,"non_numeric":0}"#);
+    }
+
+    #[test]
+    fn test_non_numeric_values_are_counted_not_summed() {
+        let re = Regex::new(r"(\w+)=(\S+)").unwrap();
+        let mut table = GroupTable::new(vec![1], Some(2));
+        for line in ["a=1", "a=lots", "a=NaN", "b=2"] {
+            table.add(&re.captures(line).unwrap());
+        }
+
+        let names = ["key".to_string()];
+        assert_eq!(
+            table.render_table(&names, Some("n")),
+            "key  count  sum(n)  non-numeric\n\
+             a        3       1            2\n\
+             b        1       2            0\n"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::{fmt::Write, path::PathBuf};
+
+use regex::Regex;
+
+#[derive(Debug)]
+pub(crate) enum HtmlEntry {
+    Match { line_number: usize, html: String },
+    Context { line_number: usize, text: String },
+    Break,
+}
+
+#[derive(Debug)]
+pub(crate) struct HtmlFile {
+    pub(crate) path: PathBuf,
+    pub(crate) matched_lines: usize,
+    pub(crate) entries: Vec<HtmlEntry>,
+}
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.4em; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.75em; text-align: left; }
+td.count { text-align: right; }
+#filter { width: 100%; max-width: 40em; padding: 0.4em; margin-bottom: 1em; }
+details { margin-bottom: 0.75em; border: 1px solid #ddd; border-radius: 4px; }
+summary { cursor: pointer; padding: 0.4em 0.6em; background: #f4f4f4; font-family: monospace; }
+summary .count { color: #666; }
+pre { margin: 0; padding: 0.4em 0.6em; overflow-x: auto; }
+.line { display: block; }
+.line .num { display: inline-block; min-width: 4em; color: #888; user-select: none; }
+.context { color: #777; }
+.break { color: #aaa; }
+mark { background: #ffe066; font-weight: bold; }
+"#;
+
+const SCRIPT: &str = r#"
+document.getElementById('filter').addEventListener('input', function (event) {
+  var needle = event.target.value.toLowerCase();
+  document.querySelectorAll('details.file').forEach(function (file) {
+    var visible = !needle || file.textContent.toLowerCase().indexOf(needle) !== -1;
+    file.style.display = visible ? '' : 'none';
+    var row = document.getElementById('row-' + file.id);
+    if (row) { row.style.display = visible ? '' : 'none'; }
+  });
+});
+"#;
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// Escapes `line` and wraps every match of `re` in `<mark>`.
+pub(crate) fn highlight(re: &Regex, line: &str) -> String {
+    let mut html = String::new();
+    let mut last = 0;
+    for m in re.find_iter(line) {
+        html.push_str(&escape_html(&line[last..m.start()]));
+        html.push_str("<mark>");
+        html.push_str(&escape_html(m.as_str()));
+        html.push_str("</mark>");
+        last = m.end();
+    }
+    html.push_str(&escape_html(&line[last..]));
+    html
+}
+
+/// Renders a self-contained page: a summary table, a filter box and one
+/// collapsible section per file. Everything is inlined.
+pub(crate) fn render_report(pattern: &str, files: &[HtmlFile]) -> String {
+    let total: usize = files.iter().map(|f| f.matched_lines).sum();
+    let title = format!("minigrep report for /{}/", escape_html(pattern));
+    let mut page = String::new();
+
+    let _ = write!(
+        page,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    let _ = writeln!(
+        page,
+        "<p>{} matching line(s) in {} file(s).</p>",
+        total,
+        files.len()
+    );
+
+    page.push_str("<table>\n<thead><tr><th>File</th><th>Matches</th></tr></thead>\n<tbody>\n");
+    for (i, file) in files.iter().enumerate() {
+        let _ = writeln!(
+            page,
+            "<tr id=\"row-file-{i}\"><td><a href=\"#file-{i}\">{}</a></td><td class=\"count\">{}</td></tr>",
+            escape_html(&file.path.display().to_string()),
+            file.matched_lines
+        );
+    }
+    page.push_str("</tbody>\n</table>\n");
+
+    page.push_str("<input id=\"filter\" type=\"search\" placeholder=\"Filter files and lines…\">\n");
+
+    for (i, file) in files.iter().enumerate() {
+        let _ = write!(
+            page,
+            "<details class=\"file\" id=\"file-{i}\" open>\n<summary>{} <span class=\"count\">({})</span></summary>\n<pre>",
+            escape_html(&file.path.display().to_string()),
+            file.matched_lines
+        );
+        for entry in &file.entries {
+            match entry {
+                HtmlEntry::Match { line_number, html } => {
+                    let _ = write!(page, "<span class=\"line\"><span class=\"num\">{line_number}:</span>{html}</span>");
+                }
+                HtmlEntry::Context { line_number, text } => {
+                    let _ = write!(
+                        page,
+                        "<span class=\"line context\"><span class=\"num\">{line_number}-</span>{}</span>",
+                        escape_html(text)
+                    );
+                }
+                HtmlEntry::Break => page.push_str("<span class=\"line break\">--</span>"),
+            }
+        }
+        page.push_str("</pre>\n</details>\n");
+    }
+
+    let _ = write!(page, "<script>{SCRIPT}</script>\n</body>\n</html>\n");
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_escapes_and_marks() {
+        let re = Regex::new("<b>").unwrap();
+        assert_eq!(highlight(&re, "a<b>&<b>"), "a<mark>&lt;b&gt;</mark>&amp;<mark>&lt;b&gt;</mark>");
+    }
+}
@@ -1,10 +1,23 @@
+pub mod builder;
+pub mod colors;
+pub mod delimited;
 pub mod formatter;
+pub mod group;
+pub mod html;
+pub mod sarif;
 pub mod sinks;
+pub mod tally;
+pub mod template;
 
-use std::{error::Error, ops::ControlFlow, path::{Path, PathBuf}};
+use std::{error::Error, ops::{ControlFlow, Range}, path::{Path, PathBuf}};
 use serde::Serialize;
-use crate::matcher::MatchResult;
+use crate::{matcher::MatchResult, output::group::GroupRow, search::stats::SearchStats};
 
+/// The column reported for the byte offset `byte` of `line`: 1-based and
+/// counted in characters (Unicode code points), as in every output mode.
+pub(crate) fn column(line: &str, byte: usize) -> usize {
+    line[..byte].chars().count() + 1
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ContextKind {
@@ -24,6 +37,12 @@ pub struct ContextLine {
 pub struct MatchedLine<'a> {
     pub path: &'a Path,
     pub line_number: usize,
+    /// Byte offset of the start of the line within its input.
+    pub byte_offset: u64,
+    /// The whole line, without its terminator.
+    pub line: &'a str,
+    /// Byte ranges of each occurrence of the pattern within `line`.
+    pub spans: Vec<Range<usize>>,
     pub match_result: MatchResult<'a>,
 }
 
@@ -34,6 +53,58 @@ pub(crate) enum JsonContent {
     Matches(Vec<String>),
 }
 
+#[derive(Serialize, Debug)]
+pub(crate) struct JsonReport<'a> {
+    pub(crate) matches: &'a [JsonMatch],
+    pub(crate) summary: &'a SearchStats,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct JsonCount<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) count: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct JsonCounts<'a> {
+    pub(crate) files: Vec<JsonCount<'a>>,
+    pub(crate) total: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) summary: Option<&'a SearchStats>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct JsonTallyEntry<'a> {
+    pub(crate) value: &'a str,
+    pub(crate) count: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct JsonTally<'a> {
+    pub(crate) values: Vec<JsonTallyEntry<'a>>,
+    pub(crate) total: u64,
+    pub(crate) distinct: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) summary: Option<&'a SearchStats>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct JsonGroupRow<'a> {
+    pub(crate) key: &'a [Option<String>],
+    #[serde(flatten)]
+    pub(crate) row: &'a GroupRow,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct JsonGroups<'a> {
+    pub(crate) group_by: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) sum: Option<&'a str>,
+    pub(crate) rows: Vec<JsonGroupRow<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) summary: Option<&'a SearchStats>,
+}
+
 #[derive(Serialize, Debug)]
 pub(crate) struct JsonMatch {
     path: PathBuf,
@@ -42,21 +113,32 @@ pub(crate) struct JsonMatch {
 }
 
 pub trait Sink {
+    /// Called before the first line of each input is searched.
+    fn begin_file(&mut self, _path: &Path) {}
+
     fn matched(
         &mut self,
         data: &MatchedLine<'_>,
-    ) -> Result<ControlFlow<()>, Box<dyn Error>>;
+    ) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>>;
 
     fn context(
         &mut self,
         _line: &ContextLine,
-    ) -> Result<ControlFlow<()>, Box<dyn Error>> {
+    ) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
         Ok(ControlFlow::Continue(()))
     }
 
-    fn context_break(&mut self) -> Result<ControlFlow<()>, Box<dyn Error>> {
+    fn context_break(&mut self) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
         Ok(ControlFlow::Continue(()))
     }
 
-    fn finish(&mut self);
+    /// Offers the run's statistics before `finish`. Returns `true` when the
+    /// sink reports them as part of its own output.
+    fn stats(&mut self, _stats: &SearchStats) -> bool {
+        false
+    }
+
+    /// Called once after everything has been searched; sinks that
+    /// aggregate write their output here, and buffered sinks flush.
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>>;
 }
@@ -0,0 +1,240 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::search::stats::SearchStats;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SarifLevel {
+    Error,
+    #[default]
+    Warning,
+    Note,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun>,
+}
+
+impl SarifLog {
+    pub(crate) fn new(rules: Vec<SarifRule>, results: Vec<SarifResult>, summary: Option<SearchStats>) -> Self {
+        Self {
+            schema: SARIF_SCHEMA,
+            version: SARIF_VERSION,
+            runs: vec![SarifRun {
+                tool: SarifTool {
+                    driver: SarifDriver {
+                        name: env!("CARGO_PKG_NAME"),
+                        version: env!("CARGO_PKG_VERSION"),
+                        rules,
+                    },
+                },
+                column_kind: "unicodeCodePoints",
+                results,
+                properties: summary.map(|summary| SarifRunProperties { summary }),
+            }],
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifRun {
+    tool: SarifTool,
+    column_kind: &'static str,
+    results: Vec<SarifResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<SarifRunProperties>,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifRunProperties {
+    summary: SearchStats,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifDriver {
+    name: &'static str,
+    version: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SarifRule {
+    id: String,
+    short_description: SarifMessage,
+    full_description: SarifMessage,
+    default_configuration: SarifConfiguration,
+}
+
+impl SarifRule {
+    pub(crate) fn for_pattern(index: usize, pattern: &str, level: SarifLevel) -> Self {
+        Self {
+            id: rule_id(index),
+            short_description: SarifMessage::new(format!("Matches /{}/", pattern)),
+            full_description: SarifMessage::new(format!(
+                "Lines matching the regular expression /{}/",
+                pattern
+            )),
+            default_configuration: SarifConfiguration { level },
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct SarifConfiguration {
+    level: SarifLevel,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifMessage {
+    text: String,
+}
+
+impl SarifMessage {
+    fn new(text: String) -> Self {
+        Self { text }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SarifResult {
+    rule_id: String,
+    rule_index: usize,
+    level: SarifLevel,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+}
+
+impl SarifResult {
+    /// Builds a result for the byte range `start..end` of `line`. Columns
+    /// are 1-based code point positions, matching the run's `columnKind`.
+    pub(crate) fn new(
+        rule_index: usize,
+        level: SarifLevel,
+        path: &Path,
+        line_number: usize,
+        line: &str,
+        start: usize,
+        end: usize,
+    ) -> Self {
+        let column = |byte: usize| super::column(line, byte);
+        let matched = &line[start..end];
+
+        Self {
+            rule_id: rule_id(rule_index),
+            rule_index,
+            level,
+            message: SarifMessage::new(format!("Pattern matched '{}'", matched)),
+            locations: vec![SarifLocation {
+                physical_location: SarifPhysicalLocation {
+                    artifact_location: SarifArtifactLocation { uri: path_to_uri(path) },
+                    region: SarifRegion {
+                        start_line: line_number,
+                        start_column: column(start),
+                        end_line: line_number,
+                        end_column: column(end),
+                        snippet: SarifMessage::new(matched.to_string()),
+                    },
+                    context_region: SarifRegion {
+                        start_line: line_number,
+                        start_column: 1,
+                        end_line: line_number,
+                        end_column: column(line.len()),
+                        snippet: SarifMessage::new(line.to_string()),
+                    },
+                },
+            }],
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: SarifPhysicalLocation,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region: SarifRegion,
+    context_region: SarifRegion,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+    snippet: SarifMessage,
+}
+
+fn rule_id(index: usize) -> String {
+    format!("minigrep/pattern-{}", index)
+}
+
+/// Turns a path into a URI, percent-encoding anything outside the
+/// unreserved set. Relative paths stay relative references.
+fn path_to_uri(path: &Path) -> String {
+    let raw = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::with_capacity(raw.len());
+    if path.is_absolute() {
+        uri.push_str("file://");
+        if !raw.starts_with('/') {
+            uri.push('/');
+        }
+    }
+    for byte in raw.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_columns_count_code_points() {
+        let result = SarifResult::new(0, SarifLevel::Warning, Path::new("dir/ä b.rs"), 3, "héllo wörld", 7, 13);
+        let json = serde_json::to_value(&result).unwrap();
+        let location = &json["locations"][0]["physicalLocation"];
+
+        assert_eq!(location["artifactLocation"]["uri"], "dir/%C3%A4%20b.rs");
+        assert_eq!(location["region"]["startColumn"], 7);
+        assert_eq!(location["region"]["endColumn"], 12);
+        assert_eq!(location["region"]["snippet"]["text"], "wörld");
+        assert_eq!(location["contextRegion"]["endColumn"], 12);
+        assert_eq!(json["ruleId"], "minigrep/pattern-0");
+    }
+}
@@ -1,41 +1,61 @@
-use crate::{config::Config, matcher::MatchResult, output::{formatter::OutputFormatter, ContextLine, JsonContent, JsonMatch, MatchedLine, Sink}};
-use colored::*;
+use crate::{config::Config, matcher::MatchResult, output::{column, colors::{ColorScheme, ColorStyle}, delimited::Delimiter, formatter::OutputFormatter, group::GroupTable, html::{self, HtmlEntry, HtmlFile}, sarif::{SarifLevel, SarifLog, SarifResult, SarifRule}, tally::{Tally, TallyOrder}, template::{Template, TemplateFields}, ContextLine, JsonContent, JsonCount, JsonCounts, JsonGroupRow, JsonGroups, JsonMatch, JsonReport, JsonTally, JsonTallyEntry, MatchedLine, Sink}, search::stats::SearchStats};
 use regex::{Captures, Regex};
+use serde::Serialize;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashSet,
     error::Error,
+    io::{self, Write},
     ops::ControlFlow,
-    path::{PathBuf}
+    path::{Path, PathBuf}
 };
 
-pub(crate) struct StandardSink<'a> {
+pub(crate) struct StandardSink<'a, W> {
     pub(crate) re: &'a Regex,
     pub(crate) formatter: OutputFormatter<'a>,
+    last_heading: Option<PathBuf>,
+    out: W,
 }
 
-impl<'a> StandardSink<'a> {
-    pub(crate) fn new(config: &'a Config, re: &'a Regex) -> Self {
+impl<'a, W: Write> StandardSink<'a, W> {
+    pub(crate) fn new(config: &'a Config, re: &'a Regex, colors: &'a ColorScheme, out: W) -> Self {
         Self {
             re,
-            formatter: OutputFormatter::new(config),
+            formatter: OutputFormatter::new(config, colors),
+            last_heading: None,
+            out,
         }
     }
+
+    fn print_heading(&mut self, path: &Path) -> io::Result<()> {
+        if !self.formatter.uses_heading() || self.last_heading.as_deref() == Some(path) {
+            return Ok(());
+        }
+        if self.last_heading.is_some() {
+            writeln!(self.out)?;
+        }
+        writeln!(self.out, "{}", self.formatter.format_heading(path))?;
+        self.last_heading = Some(path.to_path_buf());
+        Ok(())
+    }
 }
 
-impl<'a> Sink for StandardSink<'a> {
-    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error>> {
+impl<'a, W: Write> Sink for StandardSink<'a, W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        self.print_heading(data.path)?;
         let prefix = self.formatter.format_prefix(data.path, data.line_number, None);
 
         match &data.match_result {
             MatchResult::Line(content) => {
+                let match_style = &self.formatter.colors.matched;
                 let highlighted_line = self.re.replace_all(content, |caps: &Captures| {
-                    caps[0].red().bold().to_string()
+                    match_style.paint(&caps[0]).to_string()
                 });
-                println!("{}{}", prefix, highlighted_line);
+                writeln!(self.out, "{}{}", prefix, highlighted_line)?;
             }
             MatchResult::Content(matches) => {
+                let match_style = &self.formatter.colors.matched;
                 for m in matches {
-                    println!("{}{}", prefix, m.red().bold());
+                    writeln!(self.out, "{}{}", prefix, match_style.paint(m))?;
                 }
             }
         }
@@ -45,78 +65,388 @@ impl<'a> Sink for StandardSink<'a> {
     fn context(
         &mut self,
         line: &ContextLine,
-    ) -> Result<ControlFlow<()>, Box<dyn Error>> {
+    ) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        self.print_heading(&line.path)?;
         let prefix = self.formatter.format_prefix(&line.path, line.line_number, Some(line.kind));
-        println!("{}{}", prefix, line.content);
+        writeln!(self.out, "{}{}", prefix, self.formatter.colors.context.paint(&line.content))?;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn context_break(&mut self) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        writeln!(self.out, "{}", self.formatter.colors.separator.paint("--"))?;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Renders every match through a user-supplied `--format` template.
+pub(crate) struct TemplateSink<'a, W> {
+    re: &'a Regex,
+    pattern: &'a str,
+    template: Template,
+    context_template: Option<Template>,
+    out: W,
+}
+
+impl<'a, W: Write> TemplateSink<'a, W> {
+    pub(crate) fn new(
+        re: &'a Regex,
+        pattern: &'a str,
+        template: Template,
+        context_template: Option<Template>,
+        out: W,
+    ) -> Self {
+        Self { re, pattern, template, context_template, out }
+    }
+}
+
+impl<'a, W: Write> Sink for TemplateSink<'a, W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        let render = |captures: Option<&Captures<'_>>| {
+            self.template.render(&TemplateFields {
+                path: data.path,
+                line_number: data.line_number,
+                line: data.line,
+                line_offset: data.byte_offset,
+                captures,
+                pattern: self.pattern,
+            })
+        };
+
+        let mut lines = Vec::new();
+        if self.template.is_per_match() {
+            lines.extend(self.re.captures_iter(data.line).map(|caps| render(Some(&caps))));
+        }
+        if lines.is_empty() {
+            lines.push(render(None));
+        }
+        for line in lines {
+            writeln!(self.out, "{}", line)?;
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn context(&mut self, line: &ContextLine) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        if let Some(template) = &self.context_template {
+            writeln!(self.out, "{}", template.render(&TemplateFields {
+                path: &line.path,
+                line_number: line.line_number,
+                line: &line.content,
+                line_offset: 0,
+                captures: None,
+                pattern: self.pattern,
+            }))?;
+        }
         Ok(ControlFlow::Continue(()))
     }
 
-    fn context_break(&mut self) -> Result<ControlFlow<()>, Box<dyn Error>> {
-        println!("{}", "--".cyan());
+    fn context_break(&mut self) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        if self.context_template.is_some() {
+            writeln!(self.out, "--")?;
+        }
         Ok(ControlFlow::Continue(()))
     }
 
-    fn finish(&mut self) {}
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub(crate) struct FilesWithMatchesSink<W> {
+    pub(crate) matched_files: Vec<PathBuf>,
+    path_style: ColorStyle,
+    sort_by_path: bool,
+    out: W,
 }
 
-#[derive(Default)]
-pub(crate) struct FilesWithMatchesSink {
-    pub(crate) matched_files: HashSet<PathBuf>,
+impl<W: Write> FilesWithMatchesSink<W> {
+    pub(crate) fn new(config: &Config, colors: &ColorScheme, out: W) -> Self {
+        Self {
+            matched_files: Vec::new(),
+            path_style: colors.path.clone(),
+            sort_by_path: config.file_sort().is_none(),
+            out,
+        }
+    }
 }
 
-impl Sink for FilesWithMatchesSink {
-    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error>> {
-        self.matched_files.insert(data.path.to_path_buf());
+impl<W: Write> Sink for FilesWithMatchesSink<W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        if self.matched_files.last().is_none_or(|last| last != data.path) {
+            self.matched_files.push(data.path.to_path_buf());
+        }
         Ok(ControlFlow::Break(()))
     }
     
-    fn finish(&mut self) {
-        let mut sorted_files: Vec<_> = self.matched_files.iter().collect();
-        sorted_files.sort();
-        for file in sorted_files {
-            println!("{}", file.display().to_string().cyan());
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.sort_by_path {
+            self.matched_files.sort();
+        }
+        for file in &self.matched_files {
+            writeln!(self.out, "{}", self.path_style.paint(&file.display().to_string()))?;
         }
+        self.out.flush()?;
+        Ok(())
     }
 }
-#[derive(Default)]
-pub(crate) struct CountSink {
-    pub(crate) counts: HashMap<PathBuf, u64>,
+/// Per-file counts in the order files were searched. Every match for a
+/// file arrives before the next file begins, so only the last entry is
+/// ever updated.
+pub(crate) struct CountSink<W> {
+    pub(crate) counts: Vec<(PathBuf, u64)>,
+    path_style: ColorStyle,
+    count_matches: bool,
+    include_zero: bool,
+    json: bool,
+    sort_by_path: bool,
+    summary: Option<SearchStats>,
+    out: W,
+}
+
+impl<W: Write> CountSink<W> {
+    pub(crate) fn new(config: &Config, colors: &ColorScheme, out: W) -> Self {
+        Self {
+            counts: Vec::new(),
+            path_style: colors.path.clone(),
+            count_matches: config.mode_args.count_matches,
+            include_zero: config.mode_args.include_zero,
+            json: config.mode_args.json,
+            sort_by_path: config.file_sort().is_none(),
+            summary: None,
+            out,
+        }
+    }
+
+    fn count_for(&mut self, path: &Path) -> &mut u64 {
+        if self.counts.last().is_none_or(|(last, _)| last != path) {
+            self.counts.push((path.to_path_buf(), 0));
+        }
+        &mut self.counts.last_mut().expect("an entry was just ensured").1
+    }
+
+    fn finish_json(&mut self, total: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let files: Vec<JsonCount> = self.counts
+            .iter()
+            .map(|(path, count)| JsonCount { path, count: *count })
+            .collect();
+        let report = JsonCounts { files, total, summary: self.summary.as_ref() };
+        write_json(&mut self.out, &report)
+    }
 }
 
-impl Sink for CountSink {
-    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error>> {
-        let path_buf = data.path.to_path_buf();
-        let count = self.counts.entry(path_buf).or_insert(0);
-        *count += 1;
+impl<W: Write> Sink for CountSink<W> {
+    fn begin_file(&mut self, path: &Path) {
+        if self.include_zero {
+            self.count_for(path);
+        }
+    }
+
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        let increment = if self.count_matches {
+            data.spans.len().max(1) as u64
+        } else {
+            1
+        };
+        *self.count_for(data.path) += increment;
         Ok(ControlFlow::Continue(()))
     }
 
-    fn finish(&mut self) {
-        let mut total = 0;
-        let mut sorted_paths: Vec<_> = self.counts.keys().collect();
-        sorted_paths.sort();
+    fn stats(&mut self, stats: &SearchStats) -> bool {
+        if self.json {
+            self.summary = Some(stats.clone());
+        }
+        self.json
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.sort_by_path {
+            self.counts.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        let total = self.counts.iter().map(|(_, count)| count).sum();
+
+        if self.json {
+            return self.finish_json(total);
+        }
+
+        for (path, count) in &self.counts {
+            writeln!(self.out, "{}:{}", self.path_style.paint(&path.display().to_string()), count)?;
+        }
+
+        if self.counts.len() > 1 {
+            writeln!(self.out, "Total: {}", total)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Aggregates matched text (or one capture group) into a frequency table
+/// printed once the search finishes.
+pub(crate) struct TallySink<'a, W> {
+    re: &'a Regex,
+    group: usize,
+    order: TallyOrder,
+    top: Option<usize>,
+    json: bool,
+    value_style: ColorStyle,
+    tally: Tally,
+    summary: Option<SearchStats>,
+    out: W,
+}
+
+impl<'a, W: Write> TallySink<'a, W> {
+    pub(crate) fn new(config: &Config, re: &'a Regex, group: usize, colors: &ColorScheme, out: W) -> Self {
+        Self {
+            re,
+            group,
+            order: config.mode_args.tally_order,
+            top: config.mode_args.top,
+            json: config.mode_args.json,
+            value_style: colors.matched.clone(),
+            tally: Tally::default(),
+            summary: None,
+            out,
+        }
+    }
 
-        let print_total = self.counts.len() > 1;
+    fn finish_json(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let values = self.tally
+            .ranked(self.order, self.top)
+            .into_iter()
+            .map(|(value, count)| JsonTallyEntry { value, count })
+            .collect();
+        let report = JsonTally {
+            values,
+            total: self.tally.total,
+            distinct: self.tally.distinct(),
+            summary: self.summary.as_ref(),
+        };
+        write_json(&mut self.out, &report)
+    }
+}
 
-        for path in sorted_paths {
-            if let Some(count) = self.counts.get(path) {
-                println!("{}:{}", path.display().to_string().cyan(), count);
-                total += count;
+impl<'a, W: Write> Sink for TallySink<'a, W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        if self.group == 0 {
+            for span in &data.spans {
+                self.tally.add(&data.line[span.clone()]);
             }
+        } else {
+            for caps in self.re.captures_iter(data.line) {
+                if let Some(m) = caps.get(self.group) {
+                    self.tally.add(m.as_str());
+                }
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn stats(&mut self, stats: &SearchStats) -> bool {
+        if self.json {
+            self.summary = Some(stats.clone());
+        }
+        self.json
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.json {
+            return self.finish_json();
+        }
+
+        for (value, count) in self.tally.ranked(self.order, self.top) {
+            writeln!(self.out, "{:>7} {}", count, self.value_style.paint(value))?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Counts matches keyed by the `--group-by` captures, optionally summing
+/// a numeric capture, and prints the table once the search finishes.
+pub(crate) struct GroupBySink<'a, W> {
+    re: &'a Regex,
+    key_names: &'a [String],
+    sum_name: Option<&'a str>,
+    json: bool,
+    table: GroupTable,
+    summary: Option<SearchStats>,
+    out: W,
+}
+
+impl<'a, W: Write> GroupBySink<'a, W> {
+    pub(crate) fn new(config: &'a Config, re: &'a Regex, keys: Vec<usize>, sum: Option<usize>, out: W) -> Self {
+        Self {
+            re,
+            key_names: &config.mode_args.group_by,
+            sum_name: config.mode_args.sum.as_deref(),
+            json: config.mode_args.json,
+            table: GroupTable::new(keys, sum),
+            summary: None,
+            out,
+        }
+    }
+
+    fn finish_json(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let rows = self.table
+            .rows()
+            .into_iter()
+            .map(|(key, row)| JsonGroupRow { key, row })
+            .collect();
+        let report = JsonGroups {
+            group_by: self.key_names,
+            sum: self.sum_name,
+            rows,
+            summary: self.summary.as_ref(),
+        };
+        write_json(&mut self.out, &report)
+    }
+}
+
+impl<'a, W: Write> Sink for GroupBySink<'a, W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        for caps in self.re.captures_iter(data.line) {
+            self.table.add(&caps);
         }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn stats(&mut self, stats: &SearchStats) -> bool {
+        if self.json {
+            self.summary = Some(stats.clone());
+        }
+        self.json
+    }
 
-        if print_total {
-            println!("Total: {}", total);
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.json {
+            return self.finish_json();
         }
+
+        write!(self.out, "{}", self.table.render_table(self.key_names, self.sum_name))?;
+        self.out.flush()?;
+        Ok(())
     }
 }
-#[derive(Default)]
-pub(crate) struct JsonSink {
+
+pub(crate) struct JsonSink<W> {
     matches: Vec<JsonMatch>,
+    summary: Option<SearchStats>,
+    out: W,
 }
 
-impl Sink for JsonSink {
-    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error>> {
+impl<W: Write> JsonSink<W> {
+    pub(crate) fn new(out: W) -> Self {
+        Self { matches: Vec::new(), summary: None, out }
+    }
+}
+
+impl<W: Write> Sink for JsonSink<W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
        let content = match &data.match_result {
            MatchResult::Line(l) => JsonContent::Line(l.to_string()),
            MatchResult::Content(m) => JsonContent::Matches(m.iter().map(|s|s.to_string()).collect()),
@@ -129,54 +459,340 @@ impl Sink for JsonSink {
        Ok(ControlFlow::Continue(()))
    }
 
-    fn finish(&mut self) {
-        if self.matches.is_empty() {
-            println!("[]");
-            return;
-        }
+    fn stats(&mut self, stats: &SearchStats) -> bool {
+        self.summary = Some(stats.clone());
+        true
+    }
 
-        match serde_json::to_string_pretty(&self.matches) {
-            Ok(json_string) => println!("{}", json_string),
-            Err(e) => eprintln!("Error serializing to JSON: {}", e),
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match &self.summary {
+            Some(summary) => write_json(&mut self.out, &JsonReport { matches: &self.matches, summary }),
+            None if self.matches.is_empty() => {
+                writeln!(self.out, "[]")?;
+                self.out.flush()?;
+                Ok(())
+            }
+            None => write_json(&mut self.out, &self.matches),
         }
     }
 }
 
 #[derive(Debug)]
-pub(crate) struct FilesWithoutMatchSink {
-    all_files: HashSet<PathBuf>,
+pub(crate) struct FilesWithoutMatchSink<W> {
+    all_files: Vec<PathBuf>,
     files_with_matches: HashSet<PathBuf>,
+    path_style: ColorStyle,
+    out: W,
 }
 
-impl FilesWithoutMatchSink {
-    pub(crate) fn new(all_files: HashSet<PathBuf>) -> Self {
+impl<W: Write> FilesWithoutMatchSink<W> {
+    /// `all_files` is expected in the order the files should be listed.
+    pub(crate) fn new(all_files: Vec<PathBuf>, colors: &ColorScheme, out: W) -> Self {
         Self {
             all_files,
             files_with_matches: HashSet::new(),
+            path_style: colors.path.clone(),
+            out,
         }
     }
 }
 
 
-impl Sink for FilesWithoutMatchSink {
+impl<W: Write> Sink for FilesWithoutMatchSink<W> {
     fn matched(
         &mut self,
         data: &MatchedLine<'_>,
-    ) -> Result<ControlFlow<()>, Box<dyn Error>> {
+    ) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
         self.files_with_matches.insert(data.path.to_path_buf());
         Ok(ControlFlow::Continue(()))
     }
 
-    fn finish(&mut self) {
-        let mut files_without_matches: Vec<_> = self
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let files_without_matches = self
             .all_files
-            .difference(&self.files_with_matches)
-            .collect();
-        
-        files_without_matches.sort();
+            .iter()
+            .filter(|path| !self.files_with_matches.contains(*path));
 
         for path in files_without_matches {
-            println!("{}", path.display().to_string().cyan());
+            writeln!(self.out, "{}", self.path_style.paint(&path.display().to_string()))?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+pub(crate) struct SarifSink<'a, W> {
+    re: &'a Regex,
+    pattern: &'a str,
+    level: SarifLevel,
+    results: Vec<SarifResult>,
+    summary: Option<SearchStats>,
+    out: W,
+}
+
+impl<'a, W: Write> SarifSink<'a, W> {
+    pub(crate) fn new(re: &'a Regex, pattern: &'a str, level: SarifLevel, out: W) -> Self {
+        Self { re, pattern, level, results: Vec::new(), summary: None, out }
+    }
+}
+
+impl<'a, W: Write> Sink for SarifSink<'a, W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        let mut spans: Vec<(usize, usize)> = self.re
+            .find_iter(data.line)
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        if spans.is_empty() {
+            spans.push((0, data.line.len()));
+        }
+
+        for (start, end) in spans {
+            self.results.push(SarifResult::new(
+                0,
+                self.level,
+                data.path,
+                data.line_number,
+                data.line,
+                start,
+                end,
+            ));
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn stats(&mut self, stats: &SearchStats) -> bool {
+        self.summary = Some(stats.clone());
+        true
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let rules = vec![SarifRule::for_pattern(0, self.pattern, self.level)];
+        let log = SarifLog::new(rules, std::mem::take(&mut self.results), self.summary.take());
+        write_json(&mut self.out, &log)
+    }
+}
+
+/// Writes one CSV or TSV row per match, preceded by a header row.
+pub(crate) struct DelimitedSink<'a, W> {
+    re: &'a Regex,
+    delimiter: Delimiter,
+    wrote_header: bool,
+    out: W,
+}
+
+impl<'a, W: Write> DelimitedSink<'a, W> {
+    pub(crate) fn new(re: &'a Regex, delimiter: Delimiter, out: W) -> Self {
+        Self { re, delimiter, wrote_header: false, out }
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.wrote_header {
+            write!(self.out, "{}", self.delimiter.record(["path", "line", "column", "match", "text"]))?;
+            self.wrote_header = true;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> Sink for DelimitedSink<'a, W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        self.write_header()?;
+
+        let path = data.path.display().to_string();
+        let line_number = data.line_number.to_string();
+        let mut rows: Vec<(String, &str)> = self.re
+            .find_iter(data.line)
+            .map(|m| (column(data.line, m.start()).to_string(), m.as_str()))
+            .collect();
+        if rows.is_empty() {
+            rows.push((String::new(), ""));
+        }
+
+        for (column, matched) in &rows {
+            write!(self.out, "{}", self.delimiter.record([
+                path.as_str(),
+                line_number.as_str(),
+                column.as_str(),
+                matched,
+                data.line,
+            ]))?;
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.write_header()?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Forwards every event to each of its sinks, so one run can feed several
+/// outputs at once. A file is only abandoned when every sink asks to stop;
+/// until then sinks that already returned `Break` keep receiving events.
+pub(crate) struct TeeSink<'a> {
+    sinks: Vec<Box<dyn Sink + 'a>>,
+}
+
+impl<'a> TeeSink<'a> {
+    pub(crate) fn new(sinks: Vec<Box<dyn Sink + 'a>>) -> Self {
+        Self { sinks }
+    }
+}
+
+impl<'a> Sink for TeeSink<'a> {
+    fn begin_file(&mut self, path: &Path) {
+        for sink in &mut self.sinks {
+            sink.begin_file(path);
+        }
+    }
+
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        let mut flow = ControlFlow::Break(());
+        for sink in &mut self.sinks {
+            if sink.matched(data)?.is_continue() {
+                flow = ControlFlow::Continue(());
+            }
+        }
+        Ok(flow)
+    }
+
+    fn context(&mut self, line: &ContextLine) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        let mut flow = ControlFlow::Break(());
+        for sink in &mut self.sinks {
+            if sink.context(line)?.is_continue() {
+                flow = ControlFlow::Continue(());
+            }
+        }
+        Ok(flow)
+    }
+
+    fn context_break(&mut self) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        let mut flow = ControlFlow::Break(());
+        for sink in &mut self.sinks {
+            if sink.context_break()?.is_continue() {
+                flow = ControlFlow::Continue(());
+            }
+        }
+        Ok(flow)
+    }
+
+    /// Every sink sees the statistics, but only the primary (first) sink
+    /// decides whether they still need to be printed as text.
+    fn stats(&mut self, stats: &SearchStats) -> bool {
+        let mut reported = Vec::with_capacity(self.sinks.len());
+        for sink in &mut self.sinks {
+            reported.push(sink.stats(stats));
+        }
+        reported.first().copied().unwrap_or(false)
+    }
+
+    /// Finishes every sink even if one fails, returning the first error.
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            let finished = sink.finish();
+            if result.is_ok() {
+                result = finished;
+            }
+        }
+        result
+    }
+}
+
+/// Collects results and writes them as a standalone HTML page on finish.
+pub(crate) struct HtmlReportSink<'a, W> {
+    re: &'a Regex,
+    pattern: &'a str,
+    files: Vec<HtmlFile>,
+    out: W,
+}
+
+impl<'a, W: Write> HtmlReportSink<'a, W> {
+    pub(crate) fn new(re: &'a Regex, pattern: &'a str, out: W) -> Self {
+        Self { re, pattern, files: Vec::new(), out }
+    }
+
+    fn file_entry(&mut self, path: &Path) -> &mut HtmlFile {
+        if self.files.last().is_none_or(|f| f.path != path) {
+            self.files.push(HtmlFile {
+                path: path.to_path_buf(),
+                matched_lines: 0,
+                entries: Vec::new(),
+            });
         }
+        self.files.last_mut().expect("a file entry was just ensured")
     }
-}
\ No newline at end of file
+}
+
+impl<'a, W: Write> Sink for HtmlReportSink<'a, W> {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        let html = html::highlight(self.re, data.line);
+        let file = self.file_entry(data.path);
+        file.matched_lines += 1;
+        file.entries.push(HtmlEntry::Match { line_number: data.line_number, html });
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn context(&mut self, line: &ContextLine) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        self.file_entry(&line.path).entries.push(HtmlEntry::Context {
+            line_number: line.line_number,
+            text: line.content.clone(),
+        });
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn context_break(&mut self) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+        if let Some(file) = self.files.last_mut() {
+            file.entries.push(HtmlEntry::Break);
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.out.write_all(html::render_report(self.pattern, &self.files).as_bytes())?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn write_json<W: Write, T: Serialize + ?Sized>(out: &mut W, value: &T) -> Result<(), Box<dyn Error + Send + Sync>> {
+    serde_json::to_writer_pretty(&mut *out, value).map_err(io::Error::from)?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched<'a>(path: &'a Path, line_number: usize, line: &'a str, re: &Regex) -> MatchedLine<'a> {
+        MatchedLine {
+            path,
+            line_number,
+            byte_offset: 0,
+            line,
+            spans: re.find_iter(line).map(|m| m.range()).collect(),
+            match_result: MatchResult::Line(line),
+        }
+    }
+
+    #[test]
+    fn test_delimited_sink_writes_to_target() {
+        let re = Regex::new("b+").unwrap();
+        let mut sink = DelimitedSink::new(&re, Delimiter::Tab, Vec::new());
+        let flow = sink.matched(&matched(Path::new("x.txt"), 3, "abba bb", &re)).unwrap();
+        assert!(flow.is_continue());
+        sink.finish().unwrap();
+
+        let output = String::from_utf8(sink.out).unwrap();
+        assert_eq!(output, "path\tline\tcolumn\tmatch\ttext\nx.txt\t3\t2\tbb\tabba bb\nx.txt\t3\t6\tbb\tabba bb\n");
+    }
+
+    #[test]
+    fn test_json_sink_empty_output() {
+        let mut sink = JsonSink::new(Vec::new());
+        sink.finish().unwrap();
+        assert_eq!(sink.out, b"[]\n");
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TallyOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Running frequency table of matched values. Only distinct values are
+/// kept, so memory grows with the vocabulary rather than the input.
+#[derive(Debug, Default)]
+pub(crate) struct Tally {
+    counts: HashMap<String, u64>,
+    pub(crate) total: u64,
+}
+
+impl Tally {
+    pub(crate) fn add(&mut self, value: &str) {
+        match self.counts.get_mut(value) {
+            Some(count) => *count += 1,
+            None => {
+                self.counts.insert(value.to_string(), 1);
+            }
+        }
+        self.total += 1;
+    }
+
+    pub(crate) fn distinct(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Values ordered by count, ties broken alphabetically, truncated to
+    /// `top` entries when given.
+    pub(crate) fn ranked(&self, order: TallyOrder, top: Option<usize>) -> Vec<(&str, u64)> {
+        let mut entries: Vec<(&str, u64)> = self.counts.iter().map(|(v, c)| (v.as_str(), *c)).collect();
+        entries.sort_by(|a, b| {
+            let by_count = match order {
+                TallyOrder::Asc => a.1.cmp(&b.1),
+                TallyOrder::Desc => b.1.cmp(&a.1),
+            };
+            by_count.then_with(|| a.0.cmp(b.0))
+        });
+        if let Some(top) = top {
+            entries.truncate(top);
+        }
+        entries
+    }
+}
+
+/// Resolves a capture group given to `flag` to its index, by number or name.
+pub(crate) fn resolve_group(re: &Regex, group: &str, flag: &str) -> Result<usize, String> {
+    let index = match group.parse::<usize>() {
+        Ok(i) if i < re.captures_len() => Some(i),
+        Ok(_) => None,
+        Err(_) => re.capture_names().position(|n| n == Some(group)),
+    };
+    index.ok_or_else(|| format!("unknown capture group '{}' for {}", group, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranked_order_and_top() {
+        let mut tally = Tally::default();
+        for value in ["b", "a", "c", "a", "b", "a"] {
+            tally.add(value);
+        }
+        assert_eq!(tally.total, 6);
+        assert_eq!(tally.ranked(TallyOrder::Desc, None), vec![("a", 3), ("b", 2), ("c", 1)]);
+        assert_eq!(tally.ranked(TallyOrder::Asc, Some(2)), vec![("c", 1), ("b", 2)]);
+    }
+
+    #[test]
+    fn test_resolve_group() {
+        let re = Regex::new(r"(?P<ip>\d+) (\w+)").unwrap();
+        assert_eq!(resolve_group(&re, "ip", "--tally-group"), Ok(1));
+        assert_eq!(resolve_group(&re, "2", "--tally-group"), Ok(2));
+        assert!(resolve_group(&re, "3", "--tally-group").is_err());
+        assert!(resolve_group(&re, "user", "--tally-group").is_err());
+    }
+}
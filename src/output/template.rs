@@ -0,0 +1,238 @@
+use std::path::Path;
+
+use regex::{Captures, Regex};
+
+use crate::output::column;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Path,
+    Basename,
+    Line,
+    Column,
+    Offset,
+    Text,
+    Group(usize),
+    Pattern,
+}
+
+impl Segment {
+    fn is_per_match(&self) -> bool {
+        matches!(self, Segment::Column | Segment::Offset | Segment::Group(_))
+    }
+}
+
+/// Values a template can refer to. `captures` is `None` for context lines
+/// and for lines selected by an inverted match.
+pub(crate) struct TemplateFields<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) line_number: usize,
+    pub(crate) line: &'a str,
+    pub(crate) line_offset: u64,
+    pub(crate) captures: Option<&'a Captures<'a>>,
+    pub(crate) pattern: &'a str,
+}
+
+/// A parsed `--format` string. Placeholders are written `{name}`; `{{` and
+/// `}}` produce literal braces, and `\t`, `\n`, `\r` and `\\` are unescaped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub(crate) fn parse(source: &str, re: &Regex) -> Result<Self, String> {
+        Self::parse_with(source, re, true)
+    }
+
+    /// Parses a template for context lines, where only the per-line
+    /// placeholders are available.
+    pub(crate) fn parse_context(source: &str, re: &Regex) -> Result<Self, String> {
+        Self::parse_with(source, re, false)
+    }
+
+    fn parse_with(source: &str, re: &Regex, allow_matches: bool) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('r') => literal.push('\r'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    }
+                    None => literal.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(format!("unmatched '}}' in template '{}'", source)),
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err(format!("unclosed '{{' in template '{}'", source));
+                    }
+                    let segment = placeholder(&name, re)
+                        .ok_or_else(|| format!("unknown placeholder '{{{}}}' in template '{}'", name, source))?;
+                    if segment.is_per_match() && !allow_matches {
+                        return Err(format!("placeholder '{{{}}}' is not available for context lines", name));
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(segment);
+                }
+                other => literal.push(other),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Whether the template is rendered once per match rather than once per
+    /// matching line.
+    pub(crate) fn is_per_match(&self) -> bool {
+        self.segments.iter().any(Segment::is_per_match)
+    }
+
+    pub(crate) fn render(&self, fields: &TemplateFields<'_>) -> String {
+        let mut out = String::new();
+        let start = fields.captures.and_then(|c| c.get(0)).map(|m| m.start());
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Path => out.push_str(&fields.path.display().to_string()),
+                Segment::Basename => {
+                    let name = fields.path.file_name().unwrap_or(fields.path.as_os_str());
+                    out.push_str(&name.to_string_lossy());
+                }
+                Segment::Line => out.push_str(&fields.line_number.to_string()),
+                Segment::Column => {
+                    if let Some(start) = start {
+                        out.push_str(&column(fields.line, start).to_string());
+                    }
+                }
+                Segment::Offset => {
+                    if let Some(start) = start {
+                        out.push_str(&(fields.line_offset + start as u64).to_string());
+                    }
+                }
+                Segment::Text => out.push_str(fields.line),
+                Segment::Group(i) => {
+                    if let Some(m) = fields.captures.and_then(|c| c.get(*i)) {
+                        out.push_str(m.as_str());
+                    }
+                }
+                Segment::Pattern => out.push_str(fields.pattern),
+            }
+        }
+        out
+    }
+}
+
+fn placeholder(name: &str, re: &Regex) -> Option<Segment> {
+    let segment = match name {
+        "path" => Segment::Path,
+        "basename" => Segment::Basename,
+        "line" => Segment::Line,
+        "col" => Segment::Column,
+        "offset" => Segment::Offset,
+        "text" => Segment::Text,
+        "match" => Segment::Group(0),
+        "pattern" => Segment::Pattern,
+        _ => match name.parse::<usize>() {
+            Ok(i) if i < re.captures_len() => Segment::Group(i),
+            Ok(_) => return None,
+            Err(_) => {
+                let index = re.capture_names().position(|n| n == Some(name))?;
+                Segment::Group(index)
+            }
+        },
+    };
+    Some(segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields<'a>(line: &'a str, captures: Option<&'a Captures<'a>>) -> TemplateFields<'a> {
+        TemplateFields {
+            path: Path::new("src/lib.rs"),
+            line_number: 7,
+            line,
+            line_offset: 100,
+            captures,
+            pattern: "pat",
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders_and_groups() {
+        let re = Regex::new(r"(?P<key>\w+)=(\d+)").unwrap();
+        let template = Template::parse(r"{path}\t{basename}:{line}:{col}:{offset} {key}/{2} [{match}] {{{pattern}}}", &re).unwrap();
+        let line = "set a=1";
+        let caps = re.captures(line).unwrap();
+
+        assert!(template.is_per_match());
+        assert_eq!(
+            template.render(&fields(line, Some(&caps))),
+            "src/lib.rs\tlib.rs:7:5:104 a/1 [a=1] {pat}"
+        );
+    }
+
+    #[test]
+    fn test_col_counts_characters_and_offset_bytes() {
+        let re = Regex::new("w").unwrap();
+        let template = Template::parse("{col}:{offset}", &re).unwrap();
+        let line = "héllo wörld";
+        let caps = re.captures(line).unwrap();
+
+        assert_eq!(template.render(&fields(line, Some(&caps))), "7:107");
+    }
+
+    #[test]
+    fn test_per_line_template_without_captures() {
+        let re = Regex::new("x").unwrap();
+        let template = Template::parse("{line}: {text}", &re).unwrap();
+
+        assert!(!template.is_per_match());
+        assert_eq!(template.render(&fields("no x here", None)), "7: no x here");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let re = Regex::new(r"(a)").unwrap();
+
+        assert!(Template::parse("{nope}", &re).is_err());
+        assert!(Template::parse("{2}", &re).is_err());
+        assert!(Template::parse("oops}", &re).is_err());
+        assert!(Template::parse("{path", &re).is_err());
+        assert!(Template::parse_context("{match}", &re).is_err());
+        assert!(Template::parse_context("{path}-{line}-{text}", &re).is_ok());
+    }
+}
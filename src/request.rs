@@ -0,0 +1,291 @@
+// src/request.rs
+
+use std::{
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use clap::Parser;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    config::{ColorChoice, Config},
+    error::{Error, Result},
+    fs::SortKey,
+    output::tally::TallyOrder,
+    search::options::SearchOptions,
+};
+
+/// The request schema version this build understands.
+const VERSION: u32 = 1;
+
+/// A search read by `--request`. Sections and field names mirror the
+/// command-line options, e.g. `mode.count` is `--count`. Results are
+/// always written as JSON.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "patterns": ["TODO", "FIXME"],
+///   "paths": ["src"],
+///   "search": { "ignore_case": true },
+///   "output": { "context": 1, "sort": "path" },
+///   "mode": { "count": true }
+/// }
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Request {
+    /// Checked through [`VersionProbe`] before the rest is read.
+    #[allow(dead_code)]
+    version: u32,
+    /// Searched for together; a line matches if any of them does.
+    patterns: Vec<String>,
+    paths: Vec<PathBuf>,
+    #[serde(default)]
+    search: SearchSection,
+    #[serde(default)]
+    output: OutputSection,
+    #[serde(default)]
+    mode: ModeSection,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct SearchSection {
+    ignore_case: bool,
+    invert_match: bool,
+    only_matching: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct OutputSection {
+    after_context: usize,
+    before_context: usize,
+    context: usize,
+    sort: Option<SortKey>,
+    sortr: Option<SortKey>,
+    stats: bool,
+    no_messages: bool,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+struct ModeSection {
+    count: bool,
+    count_matches: bool,
+    include_zero: bool,
+    tally: bool,
+    tally_group: Option<String>,
+    top: Option<usize>,
+    tally_order: Option<TallyOrder>,
+    group_by: Vec<String>,
+    sum: Option<String>,
+}
+
+/// Just the version, read before the rest of the document so that a request
+/// from a newer schema is rejected for its version, not for the fields this
+/// build does not know.
+#[derive(Deserialize)]
+struct VersionProbe {
+    version: u32,
+}
+
+fn invalid(field: &str, message: impl Into<String>) -> Error {
+    Error::Request { field: field.to_string(), message: message.into() }
+}
+
+impl Request {
+    /// Reads and parses a request from `source`, a file or `-` for stdin.
+    pub(crate) fn read(source: &str) -> Result<Self> {
+        let text = if source == "-" {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map_err(|e| Error::io("stdin", e))?;
+            text
+        } else {
+            fs::read_to_string(source).map_err(|e| Error::io(source, e))?
+        };
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let value = serde_json::from_str(text).map_err(|e| invalid("", e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    /// Parses a request already decoded as JSON, such as the params of a
+    /// `serve` call.
+    pub(crate) fn from_value(value: Value) -> Result<Self> {
+        let probe = VersionProbe::deserialize(&value).map_err(|e| invalid("version", e.to_string()))?;
+        if probe.version != VERSION {
+            return Err(invalid("version", format!("unsupported version {} (expected {})", probe.version, VERSION)));
+        }
+
+        let request: Self = serde_path_to_error::deserialize(value).map_err(|e| {
+            let field = match e.path().to_string() {
+                root if root == "." => String::new(),
+                field => field,
+            };
+            invalid(&field, e.into_inner().to_string())
+        })?;
+        request.validate()?;
+        Ok(request)
+    }
+
+    /// Checks what the command line enforces through clap, naming the
+    /// offending field.
+    fn validate(&self) -> Result<()> {
+        if self.patterns.is_empty() {
+            return Err(invalid("patterns", "at least one pattern is required"));
+        }
+        if self.paths.is_empty() {
+            return Err(invalid("paths", "at least one path is required"));
+        }
+
+        let search = &self.search;
+        let mode = &self.mode;
+        if search.invert_match && search.only_matching {
+            return Err(invalid("search.invert_match", "cannot be used with search.only_matching"));
+        }
+        if self.output.sort.is_some() && self.output.sortr.is_some() {
+            return Err(invalid("output.sortr", "cannot be used with output.sort"));
+        }
+
+        let modes = [mode.count || mode.count_matches, mode.tally, !mode.group_by.is_empty()];
+        if modes.iter().filter(|&&on| on).count() > 1 {
+            return Err(invalid("mode", "count, tally and group_by cannot be combined"));
+        }
+        if mode.include_zero && !(mode.count || mode.count_matches) {
+            return Err(invalid("mode.include_zero", "requires mode.count or mode.count_matches"));
+        }
+        let tally_fields = [
+            ("mode.tally_group", mode.tally_group.is_some()),
+            ("mode.top", mode.top.is_some()),
+            ("mode.tally_order", mode.tally_order.is_some()),
+        ];
+        if let Some((field, _)) = tally_fields.iter().find(|(_, set)| *set && !mode.tally) {
+            return Err(invalid(field, "requires mode.tally"));
+        }
+        if mode.sum.is_some() && mode.group_by.is_empty() {
+            return Err(invalid("mode.sum", "requires mode.group_by"));
+        }
+        if search.invert_match && mode.tally {
+            return Err(invalid("mode.tally", "cannot be used with search.invert_match"));
+        }
+        if search.invert_match && !mode.group_by.is_empty() {
+            return Err(invalid("mode.group_by", "cannot be used with search.invert_match"));
+        }
+        Ok(())
+    }
+
+    /// Rejects output modes, for callers that only stream matches.
+    pub(crate) fn require_matches_mode(&self) -> Result<()> {
+        if self.mode == ModeSection::default() {
+            Ok(())
+        } else {
+            Err(invalid("mode", "not supported here; matches are always streamed"))
+        }
+    }
+
+    pub(crate) fn paths(&self) -> Vec<PathBuf> {
+        self.paths.clone()
+    }
+
+    /// The patterns combined into one, matching wherever any of them does.
+    pub(crate) fn query(&self) -> String {
+        match self.patterns.as_slice() {
+            [pattern] => pattern.clone(),
+            patterns => patterns.iter().map(|p| format!("(?:{})", p)).collect::<Vec<_>>().join("|"),
+        }
+    }
+
+    pub(crate) fn ignore_case(&self) -> bool {
+        self.search.ignore_case
+    }
+
+    /// Compiles [`query`](Self::query), blaming the first pattern that
+    /// fails on its own.
+    pub(crate) fn compile(&self) -> Result<Regex> {
+        let options = |pattern: &str| SearchOptions::new(pattern).ignore_case(self.search.ignore_case);
+        options(&self.query()).regex().map_err(|combined| {
+            for (i, pattern) in self.patterns.iter().enumerate() {
+                if let Err(e) = options(pattern).regex() {
+                    return invalid(&format!("patterns[{}]", i), e.to_string());
+                }
+            }
+            invalid("patterns", combined.to_string())
+        })
+    }
+
+    /// The equivalent command line, with JSON output.
+    pub(crate) fn into_config(self) -> Result<Config> {
+        let query = self.query();
+        let mut config = Config::try_parse_from(["minigrep", "--", query.as_str()])
+            .map_err(|e| invalid("patterns", e.to_string()))?;
+
+        config.search.ignore_case = self.search.ignore_case;
+        config.search.invert_match = self.search.invert_match;
+        config.search.only_matching = self.search.only_matching;
+
+        let output = &mut config.output;
+        output.after_context = self.output.after_context;
+        output.before_context = self.output.before_context;
+        output.context = self.output.context;
+        output.sort = self.output.sort;
+        output.sortr = self.output.sortr;
+        output.stats = self.output.stats;
+        output.no_messages = self.output.no_messages;
+        output.color = ColorChoice::Never;
+
+        let mode = &mut config.mode_args;
+        mode.json = true;
+        mode.count = self.mode.count;
+        mode.count_matches = self.mode.count_matches;
+        mode.include_zero = self.mode.include_zero;
+        mode.tally = self.mode.tally;
+        mode.tally_group = self.mode.tally_group;
+        mode.top = self.mode.top;
+        mode.tally_order = self.mode.tally_order.unwrap_or_default();
+        mode.group_by = self.mode.group_by;
+        mode.sum = self.mode.sum;
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_of(text: &str) -> String {
+        match Request::parse(text).and_then(|request| request.compile()).unwrap_err() {
+            Error::Request { field, .. } => field,
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_errors_name_the_field() {
+        let base = r#""version": 1, "patterns": ["a"], "paths": ["."]"#;
+        assert_eq!(field_of(&format!(r#"{{{base}, "mode": {{"talley": true}}}}"#)), "mode.talley");
+        assert_eq!(field_of(&format!(r#"{{{base}, "output": {{"context": "two"}}}}"#)), "output.context");
+        assert_eq!(field_of(&format!(r#"{{{base}, "mode": {{"top": 3}}}}"#)), "mode.top");
+        assert_eq!(field_of(r#"{"version": 1, "patterns": ["a", "("], "paths": ["."]}"#), "patterns[1]");
+        assert_eq!(field_of(r#"{"version": 2, "patterns": ["a"], "paths": ["."]}"#), "version");
+        assert_eq!(field_of(r#"{"patterns": ["a"], "paths": ["."]}"#), "version");
+        assert_eq!(field_of("{"), "");
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected_before_its_fields() {
+        let text = r#"{"version": 2, "patterns": ["a"], "paths": ["."], "filters": {}}"#;
+        assert_eq!(
+            Request::parse(text).unwrap_err().to_string(),
+            "invalid request: version: unsupported version 2 (expected 1)"
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use std::{
+    error,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    ops::{ControlFlow, Range},
+    path::Path,
+};
+
+use colored::*;
+use regex::{Captures, Regex};
+
+use crate::{
+    error::{Diagnostics, Error},
+    matcher::MatchResult,
+    output::{ContextLine, MatchedLine, Sink},
+    rewrite::{rewritable_files, split_line_ending, write_atomically},
+    search::context::ContextManager,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InteractiveSummary {
+    pub accepted: usize,
+    pub skipped: usize,
+    /// Matches never offered because the user quit first.
+    pub not_reviewed: usize,
+    pub files_changed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HunkEvent {
+    Context(usize),
+    Match(usize),
+    Break,
+}
+
+/// Records what `ContextManager` would print, so every prompt can show the
+/// same before/after context a normal search would.
+#[derive(Default)]
+struct HunkCollector {
+    events: Vec<HunkEvent>,
+}
+
+impl Sink for HunkCollector {
+    fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn error::Error + Send + Sync>> {
+        self.events.push(HunkEvent::Match(data.line_number));
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn context(&mut self, line: &ContextLine) -> Result<ControlFlow<()>, Box<dyn error::Error + Send + Sync>> {
+        self.events.push(HunkEvent::Context(line.line_number));
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn context_break(&mut self) -> Result<ControlFlow<()>, Box<dyn error::Error + Send + Sync>> {
+        self.events.push(HunkEvent::Break);
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+const TTY: &str = "/dev/tty";
+
+fn tty_error(source: io::Error) -> Error {
+    Error::io(TTY, source)
+}
+
+/// Asks about each match. [`open`](Prompt::open) talks to the controlling
+/// terminal directly, leaving stdin free to be the search input.
+struct Prompt<'a> {
+    input: Box<dyn BufRead + 'a>,
+    output: Box<dyn Write + 'a>,
+}
+
+impl Prompt<'static> {
+    fn open() -> io::Result<Self> {
+        let tty = OpenOptions::new().read(true).write(true).open(TTY)?;
+        Ok(Self {
+            input: Box::new(BufReader::new(tty.try_clone()?)),
+            output: Box::new(tty),
+        })
+    }
+}
+
+impl Prompt<'_> {
+    fn ask(&mut self) -> io::Result<Answer> {
+        loop {
+            write!(self.output, "{} ", "Replace? [y,n,a,q]".bold())?;
+            self.output.flush()?;
+
+            let mut reply = String::new();
+            if self.input.read_line(&mut reply)? == 0 {
+                return Ok(Answer::Quit);
+            }
+
+            match reply.trim() {
+                "y" | "Y" => return Ok(Answer::Yes),
+                "n" | "N" => return Ok(Answer::No),
+                "a" | "A" => return Ok(Answer::All),
+                "q" | "Q" => return Ok(Answer::Quit),
+                _ => writeln!(
+                    self.output,
+                    "y - replace this match\nn - skip this match\na - replace this and all remaining matches in the file\nq - quit"
+                )?,
+            }
+        }
+    }
+}
+
+pub(crate) struct InteractiveRewriter<'a> {
+    re: &'a Regex,
+    template: &'a str,
+    backup_suffix: Option<&'a str>,
+    before_len: usize,
+    after_len: usize,
+}
+
+impl<'a> InteractiveRewriter<'a> {
+    pub(crate) fn new(
+        re: &'a Regex,
+        template: &'a str,
+        backup_suffix: Option<&'a str>,
+        before_len: usize,
+        after_len: usize,
+    ) -> Self {
+        Self { re, template, backup_suffix, before_len, after_len }
+    }
+
+    pub(crate) fn rewrite_path(
+        &self,
+        path: &Path,
+        diagnostics: &mut dyn Diagnostics,
+    ) -> Result<InteractiveSummary, Error> {
+        let mut prompt = Prompt::open().map_err(tty_error)?;
+        let mut summary = InteractiveSummary::default();
+        let files = rewritable_files(path, self.backup_suffix, diagnostics);
+
+        let mut quit = false;
+
+        for file_path in files {
+            let original = match fs::read_to_string(&file_path) {
+                Ok(s) => s,
+                Err(e) => {
+                    diagnostics.report(&Error::io(file_path, e));
+                    continue;
+                }
+            };
+
+            if quit {
+                summary.not_reviewed += self.count_matches(&original);
+            } else {
+                quit = self.rewrite_file(&file_path, &original, &mut prompt, &mut summary)?.is_break();
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Offers each match in `original` in turn, then writes the accepted
+    /// ones. Returns `Break` if the user quit.
+    fn rewrite_file(
+        &self,
+        path: &Path,
+        original: &str,
+        prompt: &mut Prompt<'_>,
+        summary: &mut InteractiveSummary,
+    ) -> Result<ControlFlow<()>, Error> {
+        let lines: Vec<&str> = original.split_inclusive('\n').collect();
+        let events = self.collect_events(path, &lines).map_err(Error::Sink)?;
+
+        // The byte ranges of the accepted matches on each line.
+        let mut accepted: Vec<Vec<Range<usize>>> = vec![Vec::new(); lines.len()];
+        let mut accept_rest = false;
+        let mut flow = ControlFlow::Continue(());
+
+        for hunk in events.split(|e| *e == HunkEvent::Break) {
+            for event in hunk {
+                let HunkEvent::Match(line_number) = *event else {
+                    continue;
+                };
+                let (body, _) = split_line_ending(lines[line_number - 1]);
+
+                for caps in self.re.captures_iter(body) {
+                    let range = caps.get(0).expect("group 0 always matches").range();
+                    if flow.is_break() {
+                        summary.not_reviewed += 1;
+                        continue;
+                    }
+
+                    if !accept_rest {
+                        self.show_hunk(prompt, path, &lines, hunk, line_number, &caps).map_err(tty_error)?;
+                        match prompt.ask().map_err(tty_error)? {
+                            Answer::Yes => {}
+                            Answer::No => {
+                                summary.skipped += 1;
+                                continue;
+                            }
+                            Answer::All => accept_rest = true,
+                            Answer::Quit => {
+                                summary.not_reviewed += 1;
+                                flow = ControlFlow::Break(());
+                                continue;
+                            }
+                        }
+                    }
+
+                    accepted[line_number - 1].push(range);
+                    summary.accepted += 1;
+                }
+            }
+        }
+
+        if accepted.iter().any(|ranges| !ranges.is_empty()) {
+            let rewritten = self.apply(&lines, &accepted);
+            write_atomically(path, &rewritten, self.backup_suffix).map_err(|e| Error::io(path, e))?;
+            summary.files_changed += 1;
+        }
+
+        Ok(flow)
+    }
+
+    fn collect_events(&self, path: &Path, lines: &[&str]) -> Result<Vec<HunkEvent>, Box<dyn error::Error + Send + Sync>> {
+        let mut collector = HunkCollector::default();
+        let mut context_manager = ContextManager::new(
+            &mut collector,
+            self.before_len,
+            self.after_len,
+            path,
+        );
+
+        let mut byte_offset = 0;
+        for (i, line) in lines.iter().enumerate() {
+            let (body, _) = split_line_ending(line);
+            let spans: Vec<_> = self.re.find_iter(body).map(|m| m.range()).collect();
+            let flow = if !spans.is_empty() {
+                context_manager.handle_match(i + 1, byte_offset, body, spans, MatchResult::Line(body))?
+            } else {
+                context_manager.handle_non_match(i + 1, body.to_string())?
+            };
+            if flow.is_break() {
+                break;
+            }
+            byte_offset += line.len() as u64;
+        }
+
+        Ok(collector.events)
+    }
+
+    fn count_matches(&self, contents: &str) -> usize {
+        contents
+            .split_inclusive('\n')
+            .map(|line| self.re.find_iter(split_line_ending(line).0).count())
+            .sum()
+    }
+
+    /// Shows the hunk around the match `current`, on line `current_line`,
+    /// with only that match replaced on the `+` line.
+    fn show_hunk(
+        &self,
+        prompt: &mut Prompt<'_>,
+        path: &Path,
+        lines: &[&str],
+        hunk: &[HunkEvent],
+        current_line: usize,
+        current: &Captures<'_>,
+    ) -> io::Result<()> {
+        let out = &mut prompt.output;
+        writeln!(out, "\n{}", path.display().to_string().cyan())?;
+
+        for event in hunk {
+            let line_number = match *event {
+                HunkEvent::Context(n) | HunkEvent::Match(n) => n,
+                HunkEvent::Break => continue,
+            };
+            let (body, _) = split_line_ending(lines[line_number - 1]);
+            let number = line_number.to_string().green();
+
+            if line_number == current_line {
+                let range = current.get(0).expect("group 0 always matches").range();
+                let mut replaced = body[..range.start].to_string();
+                current.expand(self.template, &mut replaced);
+                replaced.push_str(&body[range.end..]);
+                writeln!(out, "{}{} {}", number, "-".red(), body.red())?;
+                writeln!(out, "{}{} {}", number, "+".green(), replaced.green())?;
+            } else {
+                writeln!(out, "{}  {}", number, body)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the accepted matches on each line, leaving the rest as
+    /// they were.
+    fn apply(&self, lines: &[&str], accepted: &[Vec<Range<usize>>]) -> String {
+        let mut rewritten = String::new();
+        for (line, ranges) in lines.iter().zip(accepted) {
+            if ranges.is_empty() {
+                rewritten.push_str(line);
+                continue;
+            }
+
+            let (body, ending) = split_line_ending(line);
+            let mut last = 0;
+            for caps in self.re.captures_iter(body) {
+                let m = caps.get(0).expect("group 0 always matches");
+                if ranges.contains(&m.range()) {
+                    rewritten.push_str(&body[last..m.start()]);
+                    caps.expand(self.template, &mut rewritten);
+                    last = m.end();
+                }
+            }
+            rewritten.push_str(&body[last..]);
+            rewritten.push_str(ending);
+        }
+        rewritten
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `rewrite_file` on `contents` with `replies` typed at the prompt.
+    fn review(contents: &str, replies: &str) -> (String, InteractiveSummary, ControlFlow<()>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, contents).unwrap();
+
+        let re = Regex::new(r"foo(\d)").unwrap();
+        let rewriter = InteractiveRewriter::new(&re, "bar$1", None, 0, 0);
+        let mut prompt = Prompt { input: Box::new(replies.as_bytes()), output: Box::new(io::sink()) };
+        let mut summary = InteractiveSummary::default();
+        let flow = rewriter.rewrite_file(&path, contents, &mut prompt, &mut summary).unwrap();
+        (fs::read_to_string(&path).unwrap(), summary, flow)
+    }
+
+    #[test]
+    fn test_each_match_on_a_line_is_asked_about() {
+        let (rewritten, summary, flow) = review("foo1 foo2\nfoo3\n", "n\ny\ny\n");
+        assert_eq!(rewritten, "foo1 bar2\nbar3\n");
+        assert_eq!((summary.accepted, summary.skipped, summary.files_changed), (2, 1, 1));
+        assert!(flow.is_continue());
+    }
+
+    #[test]
+    fn test_all_accepts_the_rest_of_the_file() {
+        let (rewritten, summary, _) = review("foo1 foo2\r\nfoo3", "n\na\n");
+        assert_eq!(rewritten, "foo1 bar2\r\nbar3");
+        assert_eq!((summary.accepted, summary.skipped), (2, 1));
+    }
+
+    #[test]
+    fn test_quit_keeps_earlier_edits_and_counts_the_rest() {
+        let (rewritten, summary, flow) = review("foo1 foo2\nfoo3\n", "y\nq\n");
+        assert_eq!(rewritten, "bar1 foo2\nfoo3\n");
+        assert_eq!((summary.accepted, summary.skipped, summary.not_reviewed), (1, 0, 2));
+        assert!(flow.is_break());
+    }
+
+    #[test]
+    fn test_no_edits_leave_the_file_alone() {
+        let (rewritten, summary, _) = review("foo1\n", "n\n");
+        assert_eq!(rewritten, "foo1\n");
+        assert_eq!(summary.files_changed, 0);
+    }
+}
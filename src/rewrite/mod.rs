@@ -0,0 +1,328 @@
+pub mod interactive;
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use tempfile::NamedTempFile;
+
+use crate::{
+    error::{Diagnostics, Error, Result},
+    fs::searchable_files,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RewriteSummary {
+    pub files_changed: usize,
+    pub replacements: usize,
+}
+
+pub(crate) struct Rewriter<'a> {
+    re: &'a Regex,
+    template: &'a str,
+    backup_suffix: Option<&'a str>,
+    dry_run: bool,
+}
+
+impl<'a> Rewriter<'a> {
+    pub(crate) fn new(
+        re: &'a Regex,
+        template: &'a str,
+        backup_suffix: Option<&'a str>,
+        dry_run: bool,
+    ) -> Self {
+        Self { re, template, backup_suffix, dry_run }
+    }
+
+    pub(crate) fn rewrite_path(
+        &self,
+        path: &Path,
+        out: &mut dyn Write,
+        diagnostics: &mut dyn Diagnostics,
+    ) -> Result<RewriteSummary> {
+        let mut summary = RewriteSummary::default();
+        let files = rewritable_files(path, self.backup_suffix, diagnostics);
+
+        for file_path in files {
+            let original = match fs::read_to_string(&file_path) {
+                Ok(s) => s,
+                Err(e) => {
+                    diagnostics.report(&Error::io(file_path, e));
+                    continue;
+                }
+            };
+
+            let (lines, count) = self.replace_lines(&original);
+            if count == 0 || lines.iter().all(|line| !line.is_changed()) {
+                continue;
+            }
+
+            if self.dry_run {
+                print_diff(out, &file_path, &lines)?;
+            } else if let Err(e) = write_atomically(&file_path, &joined(&lines), self.backup_suffix) {
+                diagnostics.report(&Error::io(file_path, e));
+                continue;
+            }
+
+            summary.files_changed += 1;
+            summary.replacements += count;
+        }
+
+        Ok(summary)
+    }
+
+    /// Applies the substitution line by line, so anchors behave exactly as
+    /// they do when searching, and keeps the original line endings intact.
+    fn replace_lines<'c>(&self, contents: &'c str) -> (Vec<RewrittenLine<'c>>, usize) {
+        let mut lines = Vec::new();
+        let mut count = 0;
+
+        for line in contents.split_inclusive('\n') {
+            let (body, ending) = split_line_ending(line);
+            count += self.re.find_iter(body).count();
+            let mut new = self.re.replace_all(body, self.template).into_owned();
+            new.push_str(ending);
+            lines.push(RewrittenLine { old: line, new });
+        }
+
+        (lines, count)
+    }
+}
+
+/// One line of a file and what the rewrite turns it into, both with their
+/// line endings. `new` spans several lines when the template adds some.
+pub(crate) struct RewrittenLine<'c> {
+    old: &'c str,
+    new: String,
+}
+
+impl RewrittenLine<'_> {
+    fn is_changed(&self) -> bool {
+        self.old != self.new
+    }
+}
+
+fn joined(lines: &[RewrittenLine<'_>]) -> String {
+    lines.iter().map(|line| line.new.as_str()).collect()
+}
+
+/// The files under `path` to rewrite: those a search would read, less the
+/// backups an earlier run left behind.
+pub(crate) fn rewritable_files(
+    path: &Path,
+    backup_suffix: Option<&str>,
+    diagnostics: &mut dyn Diagnostics,
+) -> Vec<PathBuf> {
+    searchable_files(path, diagnostics)
+        .filter(|file| match backup_suffix {
+            Some(suffix) => !file.as_os_str().to_string_lossy().ends_with(suffix),
+            None => true,
+        })
+        .collect()
+}
+
+pub(crate) fn split_line_ending(line: &str) -> (&str, &str) {
+    if let Some(body) = line.strip_suffix("\r\n") {
+        (body, "\r\n")
+    } else if let Some(body) = line.strip_suffix('\n') {
+        (body, "\n")
+    } else {
+        (line, "")
+    }
+}
+
+/// Writes the dry-run diff for `path` to `out`, which is stdout.
+pub(crate) fn print_diff(out: &mut dyn Write, path: &Path, lines: &[RewrittenLine<'_>]) -> Result<()> {
+    out.write_all(unified_diff(&path.display().to_string(), lines).as_bytes())
+        .map_err(|e| Error::io("stdout", e))
+}
+
+/// Lines of unchanged context around each change, as in `diff -u`.
+const DIFF_CONTEXT: usize = 3;
+
+/// A unified diff of a rewrite. Every original line maps to its own
+/// replacement, so the hunks come straight from the changed line indices
+/// and large files need no general-purpose diff.
+fn unified_diff(name: &str, lines: &[RewrittenLine<'_>]) -> String {
+    let mut out = format!("--- {name}\n+++ {name}\n");
+    let changed: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].is_changed()).collect();
+
+    // Lines the rewrite added before the current hunk.
+    let mut added = 0isize;
+    let mut counted = 0;
+    let mut rest = &changed[..];
+    while let Some(&first) = rest.first() {
+        let mut last = first;
+        let mut taken = 1;
+        while rest.get(taken).is_some_and(|&next| next - last <= 2 * DIFF_CONTEXT + 1) {
+            last = rest[taken];
+            taken += 1;
+        }
+        rest = &rest[taken..];
+
+        let start = first.saturating_sub(DIFF_CONTEXT);
+        let end = (last + DIFF_CONTEXT + 1).min(lines.len());
+        for line in &lines[counted..start] {
+            added += line_count(&line.new) as isize - 1;
+        }
+        let new_len: usize = lines[start..end].iter().map(|line| line_count(&line.new)).sum();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(start, end - start),
+            hunk_range((start as isize + added) as usize, new_len)
+        ));
+
+        let mut i = start;
+        while i < end {
+            if !lines[i].is_changed() {
+                push_diff_line(&mut out, ' ', lines[i].old);
+                i += 1;
+                continue;
+            }
+            let run = i..(i..end).find(|&j| !lines[j].is_changed()).unwrap_or(end);
+            for line in &lines[run.clone()] {
+                push_diff_line(&mut out, '-', line.old);
+            }
+            for line in &lines[run.clone()] {
+                for new in line.new.split_inclusive('\n') {
+                    push_diff_line(&mut out, '+', new);
+                }
+            }
+            i = run.end;
+        }
+
+        for line in &lines[start..end] {
+            added += line_count(&line.new) as isize - 1;
+        }
+        counted = end;
+    }
+    out
+}
+
+fn line_count(text: &str) -> usize {
+    text.split_inclusive('\n').count()
+}
+
+/// `start,len` for a hunk header, 1-based, with the `diff -u` convention
+/// that an empty range names the line before it.
+fn hunk_range(start: usize, len: usize) -> String {
+    if len == 0 { format!("{start},0") } else { format!("{},{len}", start + 1) }
+}
+
+fn push_diff_line(out: &mut String, marker: char, line: &str) {
+    out.push(marker);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Replaces `path` with `contents` through a temporary file in the same
+/// directory, so readers never observe a half-written file.
+pub(crate) fn write_atomically(
+    path: &Path,
+    contents: &str,
+    backup_suffix: Option<&str>,
+) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let permissions = fs::metadata(path)?.permissions();
+
+    let mut tmp = NamedTempFile::new_in(dir)?;
+    tmp.write_all(contents.as_bytes())?;
+    tmp.as_file().set_permissions(permissions)?;
+    tmp.as_file().sync_all()?;
+
+    if let Some(suffix) = backup_suffix {
+        fs::copy(path, backup_path(path, suffix))?;
+    }
+
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_lines_keeps_line_endings() {
+        let re = Regex::new(r"foo(\d)").unwrap();
+        let rewriter = Rewriter::new(&re, "bar$1", None, true);
+
+        let (lines, count) = rewriter.replace_lines("foo1 foo2\r\nnone\nfoo3");
+        assert_eq!(joined(&lines), "bar1 bar2\r\nnone\nbar3");
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_replace_lines_anchors_apply_per_line() {
+        let re = Regex::new(r"^x").unwrap();
+        let rewriter = Rewriter::new(&re, "y", None, true);
+
+        let (lines, count) = rewriter.replace_lines("xa\nxb\n");
+        assert_eq!(joined(&lines), "ya\nyb\n");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_diff_hunks_follow_changed_lines() {
+        let re = Regex::new("x").unwrap();
+        let rewriter = Rewriter::new(&re, "y\nz", None, true);
+        let contents = "x\n1\n2\n3\n4\n5\n6\n7\n8\nx";
+
+        let (lines, _) = rewriter.replace_lines(contents);
+        assert_eq!(
+            unified_diff("f", &lines),
+            "--- f\n+++ f\n\
+             @@ -1,4 +1,5 @@\n-x\n+y\n+z\n 1\n 2\n 3\n\
+             @@ -7,4 +8,5 @@\n 6\n 7\n 8\n-x\n\\ No newline at end of file\n+y\n+z\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_of_large_file_is_linear() {
+        let re = Regex::new("foo").unwrap();
+        let rewriter = Rewriter::new(&re, "bar", None, true);
+        let contents = "foo\nkeep\n".repeat(100_000);
+
+        let (lines, count) = rewriter.replace_lines(&contents);
+        let diff = unified_diff("big.txt", &lines);
+        assert_eq!(count, 100_000);
+        assert!(diff.starts_with("--- big.txt\n+++ big.txt\n@@ -1,200000 +1,200000 @@\n-foo\n+bar\n keep\n"));
+        assert_eq!(diff.lines().count(), 3 + 300_000);
+    }
+
+    #[test]
+    fn test_backups_are_not_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "foo\n").unwrap();
+        let re = Regex::new("foo").unwrap();
+        let rewriter = Rewriter::new(&re, "bar", Some(".bak"), false);
+        let mut diagnostics = |e: &Error| panic!("unexpected error: {e}");
+
+        rewriter.rewrite_path(dir.path(), &mut io::sink(), &mut diagnostics).unwrap();
+        fs::write(dir.path().join("a.txt"), "foo\n").unwrap();
+        let summary = rewriter.rewrite_path(dir.path(), &mut io::sink(), &mut diagnostics).unwrap();
+
+        assert_eq!(summary.files_changed, 1);
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt.bak")).unwrap(), "foo\n");
+        assert!(!dir.path().join("a.txt.bak.bak").exists());
+    }
+
+    #[test]
+    fn test_backup_path_appends_suffix() {
+        assert_eq!(backup_path(Path::new("src/main.rs"), ".bak"), PathBuf::from("src/main.rs.bak"));
+    }
+}
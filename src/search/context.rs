@@ -1,4 +1,4 @@
-use std::{collections::VecDeque, error::Error, path::{Path, PathBuf}};
+use std::{collections::VecDeque, error::Error, ops::{ControlFlow, Range}, path::{Path, PathBuf}};
 
 use crate::output::{ContextKind, ContextLine, MatchedLine, Sink};
 
@@ -34,45 +34,55 @@ impl<'a, 's> ContextManager<'a, 's> {
     pub fn handle_match(
         &mut self, 
         line_num: usize, 
+        byte_offset: u64,
+        line: &str,
+        spans: Vec<Range<usize>>,
         match_result: crate::matcher::MatchResult
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
         let context_enabled = self.before_len > 0 || self.after_len > 0;
 
-        if context_enabled && self.last_match_line_num > 0 && line_num > self.last_match_line_num + self.after_len + 1 {
-            let _ = self.sink.context_break()?;
+        let is_gap = context_enabled && self.last_match_line_num > 0 && line_num > self.last_match_line_num + self.after_len + 1;
+        if is_gap && self.sink.context_break()?.is_break() {
+            return Ok(ControlFlow::Break(()));
         }
 
         for (b_line_num, b_content) in &self.before_buffer {
             if *b_line_num > self.last_match_line_num {
-                let _ = self.sink.context(&ContextLine {
+                let flow = self.sink.context(&ContextLine {
                    path: self.path.clone(),
                    line_number: *b_line_num,
                    content: b_content.clone(),
                    kind: ContextKind::Before,
                })?;
+                if flow.is_break() {
+                    return Ok(flow);
+                }
             }
         }
         self.before_buffer.clear();
 
-        let _ = self.sink.matched(&MatchedLine {
+        let flow = self.sink.matched(&MatchedLine {
             path: &self.path,
             line_number: line_num,
+            byte_offset,
+            line,
+            spans,
             match_result,
         })?;
 
         self.last_match_line_num = line_num;
         self.after_countdown = self.after_len;
 
-        Ok(())
+        Ok(flow)
     }
 
     pub fn handle_non_match(
         &mut self,
         line_num: usize,
         line_content: String
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
         if self.after_countdown > 0 {
-            let _ = self.sink.context(&ContextLine {
+            let flow = self.sink.context(&ContextLine {
                 path: self.path.clone(),
                 line_number: line_num,
                 content: line_content.clone(),
@@ -80,6 +90,9 @@ impl<'a, 's> ContextManager<'a, 's> {
             })?;
             self.last_match_line_num = line_num;
             self.after_countdown -= 1;
+            if flow.is_break() {
+                return Ok(flow);
+            }
         }
         
         if self.before_len > 0 {
@@ -88,6 +101,6 @@ impl<'a, 's> ContextManager<'a, 's> {
             }
             self.before_buffer.push_back((line_num, line_content));
         }
-        Ok(())
+        Ok(ControlFlow::Continue(()))
     }
 }
\ No newline at end of file
@@ -0,0 +1,128 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::error::{Error, Result};
+
+/// How many bytes are read between cancellation checks and progress
+/// reports within a single file.
+const CHECK_INTERVAL: u64 = 64 * 1024;
+
+/// A flag shared between a running search and whoever wants to stop it.
+/// Clones refer to the same flag, so one can be handed to a
+/// [`Searcher`](crate::Searcher) and the other kept, e.g. by a UI thread.
+///
+/// The searcher checks it between files and every 64 KiB within a file,
+/// then returns [`Error::Cancelled`].
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A snapshot of how far a search has got.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Files the walk has reached so far, whether or not they could be
+    /// opened. The walk is lazy, so this is at most one ahead of
+    /// `files_searched`, not a total.
+    pub files_discovered: u64,
+    /// Files whose search has completed.
+    pub files_searched: u64,
+    pub bytes_searched: u64,
+}
+
+/// Receives [`Progress`] after every file and periodically within large
+/// ones. Any `FnMut(&Progress)` closure works as a listener.
+pub trait ProgressListener {
+    fn progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> ProgressListener for F {
+    fn progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// Cancellation and progress state owned by a searcher.
+#[derive(Default)]
+pub(crate) struct Control {
+    token: CancellationToken,
+    listener: Option<Box<dyn ProgressListener>>,
+    progress: Progress,
+    last_check: u64,
+}
+
+impl Control {
+    pub(crate) fn set_token(&mut self, token: CancellationToken) {
+        self.token = token;
+    }
+
+    pub(crate) fn set_listener(&mut self, listener: Box<dyn ProgressListener>) {
+        self.listener = Some(listener);
+    }
+
+    /// Fails once the token has been cancelled.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.token.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn discovered(&mut self) {
+        self.progress.files_discovered += 1;
+    }
+
+    /// Records a line read from the current file, reporting progress and
+    /// checking for cancellation every [`CHECK_INTERVAL`] bytes.
+    pub(crate) fn read(&mut self, bytes: usize) -> Result<()> {
+        self.progress.bytes_searched += bytes as u64;
+        if self.progress.bytes_searched - self.last_check < CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.last_check = self.progress.bytes_searched;
+        self.report();
+        self.check()
+    }
+
+    pub(crate) fn file_done(&mut self) {
+        self.progress.files_searched += 1;
+        self.report();
+    }
+
+    fn report(&mut self) {
+        if let Some(listener) = self.listener.as_mut() {
+            listener.progress(&self.progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_checks_every_interval() {
+        let token = CancellationToken::new();
+        let mut control = Control::default();
+        control.set_token(token.clone());
+        token.cancel();
+
+        assert!(control.read(100).is_ok());
+        assert!(matches!(control.read(CHECK_INTERVAL as usize), Err(Error::Cancelled)));
+    }
+}
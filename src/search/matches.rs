@@ -0,0 +1,280 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    iter,
+    ops::Range,
+    path::PathBuf,
+};
+
+use serde::Serialize;
+
+use crate::{
+    error::{Error, Result},
+    fs::{SkipReason, WalkEntry},
+    matcher::Matcher,
+    search::{control::Control, stats::SearchStats},
+};
+
+/// An owned match produced by [`Searcher::matches`](crate::Searcher::matches).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub path: PathBuf,
+    pub line_number: usize,
+    /// Byte offset of the start of the line within its file.
+    pub byte_offset: u64,
+    /// The whole line, without its terminator.
+    pub line: String,
+    /// Byte ranges of each occurrence of the pattern within `line`.
+    pub spans: Vec<Range<usize>>,
+    /// Up to `before_context` lines directly preceding the match, oldest
+    /// first. Unlike the sink output, neighbouring matches may share lines.
+    pub before: Vec<String>,
+    /// Up to `after_context` lines directly following the match.
+    pub after: Vec<String>,
+}
+
+/// Iterator returned by [`Searcher::matches`](crate::Searcher::matches).
+/// Files are opened and read only as matches are pulled, so dropping the
+/// iterator stops the walk. Per-file problems are yielded as errors and
+/// the iteration carries on with the next file; cancellation is yielded as
+/// [`Error::Cancelled`] and ends it.
+pub struct Matches<'s> {
+    matcher: &'s dyn Matcher,
+    stats: &'s mut SearchStats,
+    control: &'s mut Control,
+    entries: Box<dyn Iterator<Item = WalkEntry>>,
+    context_lens: (usize, usize),
+    file: Option<OpenFile>,
+    ready: VecDeque<Match>,
+}
+
+struct OpenFile {
+    reader: BufReader<File>,
+    buf: String,
+    collector: MatchCollector,
+}
+
+/// Turns the lines of one file into [`Match`]es, holding each back until
+/// its after-context is complete. Shared by the iterator and the async
+/// stream, which differ only in how lines are read.
+pub(crate) struct MatchCollector {
+    path: PathBuf,
+    before_len: usize,
+    after_len: usize,
+    line_number: usize,
+    byte_offset: u64,
+    has_match: bool,
+    before: VecDeque<String>,
+    /// Matches still collecting after-context, with the lines they lack.
+    pending: VecDeque<(Match, usize)>,
+}
+
+impl MatchCollector {
+    pub(crate) fn new(path: PathBuf, (before_len, after_len): (usize, usize)) -> Self {
+        Self {
+            path,
+            before_len,
+            after_len,
+            line_number: 0,
+            byte_offset: 0,
+            has_match: false,
+            before: VecDeque::with_capacity(before_len),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The error for an undecodable next line.
+    pub(crate) fn encoding_error(&self) -> Error {
+        Error::Encoding { path: self.path.clone(), line_number: self.line_number + 1 }
+    }
+
+    pub(crate) fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Handles one line, `bytes_read` long including its terminator,
+    /// queueing any matches it completes onto `ready`.
+    pub(crate) fn line(
+        &mut self,
+        raw: &str,
+        bytes_read: usize,
+        matcher: &dyn Matcher,
+        stats: &mut SearchStats,
+        ready: &mut VecDeque<Match>,
+    ) {
+        self.line_number += 1;
+        let line = raw
+            .strip_suffix('\n')
+            .map(|l| l.strip_suffix('\r').unwrap_or(l))
+            .unwrap_or(raw)
+            .to_string();
+
+        for (found, missing) in self.pending.iter_mut().filter(|(_, missing)| *missing > 0) {
+            found.after.push(line.clone());
+            *missing -= 1;
+        }
+        while self.pending.front().is_some_and(|(_, missing)| *missing == 0) {
+            let (found, _) = self.pending.pop_front().expect("front was just checked");
+            ready.push_back(found);
+        }
+
+        if matcher.find(&line).is_some() {
+            let spans = matcher.spans(&line);
+            self.has_match = true;
+            stats.matched_lines += 1;
+            stats.matches += spans.len().max(1) as u64;
+
+            let found = Match {
+                path: self.path.clone(),
+                line_number: self.line_number,
+                byte_offset: self.byte_offset,
+                line: line.clone(),
+                spans,
+                before: self.before.iter().cloned().collect(),
+                after: Vec::new(),
+            };
+            if self.after_len == 0 {
+                ready.push_back(found);
+            } else {
+                self.pending.push_back((found, self.after_len));
+            }
+        }
+
+        if self.before_len > 0 {
+            if self.before.len() == self.before_len {
+                self.before.pop_front();
+            }
+            self.before.push_back(line);
+        }
+        self.byte_offset += bytes_read as u64;
+    }
+
+    /// Releases matches still waiting for after-context at end of file.
+    pub(crate) fn finish(self, stats: &mut SearchStats, ready: &mut VecDeque<Match>) {
+        ready.extend(self.pending.into_iter().map(|(found, _)| found));
+        stats.bytes_searched += self.byte_offset;
+        if self.has_match {
+            stats.files_with_matches += 1;
+        }
+    }
+}
+
+impl<'s> Matches<'s> {
+    pub(crate) fn new(
+        matcher: &'s dyn Matcher,
+        stats: &'s mut SearchStats,
+        control: &'s mut Control,
+        entries: Box<dyn Iterator<Item = WalkEntry>>,
+        context_lens: (usize, usize),
+    ) -> Self {
+        Self {
+            matcher,
+            stats,
+            control,
+            entries,
+            context_lens,
+            file: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Opens the next searchable file. Returns `None` once the walk is done.
+    fn open_next(&mut self) -> Option<Result<()>> {
+        loop {
+            let entry = self.entries.next()?;
+            if let Err(e) = self.control.check() {
+                self.entries = Box::new(iter::empty());
+                return Some(Err(e));
+            }
+            let path = match entry {
+                WalkEntry::File(path) => {
+                    self.control.discovered();
+                    path
+                }
+                WalkEntry::Skipped(reason) => {
+                    self.stats.files_skipped.record(reason);
+                    continue;
+                }
+                WalkEntry::Pruned => {
+                    self.stats.dirs_pruned += 1;
+                    continue;
+                }
+                WalkEntry::Failed(e) => {
+                    self.stats.files_skipped.record(SkipReason::Unreadable);
+                    return Some(Err(e));
+                }
+            };
+
+            let file = match File::open(&path) {
+                Ok(f) => f,
+                Err(e) => {
+                    self.stats.files_skipped.record(SkipReason::Unreadable);
+                    return Some(Err(Error::io(path, e)));
+                }
+            };
+
+            self.stats.files_searched += 1;
+            self.file = Some(OpenFile {
+                reader: BufReader::new(file),
+                buf: String::new(),
+                collector: MatchCollector::new(path, self.context_lens),
+            });
+            return Some(Ok(()));
+        }
+    }
+
+    /// Reads one line of the open file, queueing any matches it completes.
+    fn step(&mut self) -> Result<()> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+
+        file.buf.clear();
+        let bytes_read = match file.reader.read_line(&mut file.buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                return Err(file.collector.encoding_error());
+            }
+            Err(e) => return Err(Error::io(file.collector.path().clone(), e)),
+        };
+
+        if bytes_read == 0 {
+            let file = self.file.take().expect("file is open");
+            file.collector.finish(self.stats, &mut self.ready);
+            self.control.file_done();
+            return Ok(());
+        }
+
+        self.control.read(bytes_read)?;
+        file.collector.line(&file.buf, bytes_read, self.matcher, self.stats, &mut self.ready);
+        Ok(())
+    }
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Result<Match>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(found) = self.ready.pop_front() {
+                return Some(Ok(found));
+            }
+
+            if self.file.is_none() {
+                if let Err(e) = self.open_next()? {
+                    return Some(Err(e));
+                }
+                continue;
+            }
+
+            if let Err(e) = self.step() {
+                self.file = None;
+                if matches!(e, Error::Cancelled) {
+                    self.entries = Box::new(iter::empty());
+                }
+                return Some(Err(e));
+            }
+        }
+    }
+}
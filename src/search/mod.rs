@@ -1,159 +1,350 @@
 pub mod context;
+pub mod control;
+pub mod matches;
+pub mod options;
+pub mod stats;
+#[cfg(feature = "async")]
+pub mod stream;
 
-use std::{collections::HashSet, error::Error, fs::File, io::{BufRead, BufReader}, path::{Path, PathBuf}};
-
-use regex::Regex;
-use walkdir::WalkDir;
+use std::{fs::File, io::{self, BufRead, BufReader, Read}, path::Path};
 
 use crate::{
-    config::OutputMode, fs::{is_binary, is_hidden}, matcher::{
-        impls::{
-            DefaultMatcher,
-            OnlyMatchingMatcher
-        }, Matcher}, output::{
-        sinks::{CountSink, FilesWithMatchesSink, FilesWithoutMatchSink, JsonSink, StandardSink
-        }, Sink}, search::context::ContextManager, Config
-    };
-
-pub(crate) struct SearcherBuilder<'a> {
-    config: &'a Config,
-    re: &'a Regex,
+    error::{Diagnostics, Error, Result, StderrDiagnostics},
+    fs::{sorted_walk, SkipReason, SortKey, WalkEntry},
+    matcher::Matcher,
+    output::Sink,
+    vfs::Vfs,
+    search::{context::ContextManager, control::{CancellationToken, Control, ProgressListener}, matches::Matches, options::SearchOptions, stats::SearchStats},
+};
+
+/// Runs searches over paths, [`Vfs`] trees, readers and byte slices,
+/// feeding every result to a caller-supplied [`Sink`]. Statistics
+/// accumulate across calls; the caller calls [`Sink::finish`] once it has
+/// searched everything.
+///
+/// Problems with individual files under a directory, such as unreadable
+/// files, go to a [`Diagnostics`] channel (stderr by default) and the
+/// search moves on.
+///
+/// ```
+/// use minigrep::{MatchedLine, SearchOptions, Searcher, Sink};
+/// use std::{error::Error, ops::ControlFlow};
+///
+/// #[derive(Default)]
+/// struct Lines(Vec<usize>);
+///
+/// impl Sink for Lines {
+///     fn matched(&mut self, data: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+///         self.0.push(data.line_number);
+///         Ok(ControlFlow::Continue(()))
+///     }
+///
+///     fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+///         Ok(())
+///     }
+/// }
+///
+/// let mut searcher = Searcher::new(SearchOptions::new("needle")).unwrap();
+/// let mut sink = Lines::default();
+/// searcher.search_slice("haystack", b"hay\nneedle\nhay\n", &mut sink).unwrap();
+/// assert_eq!(sink.0, vec![2]);
+/// assert_eq!(searcher.stats().matched_lines, 1);
+/// ```
+pub struct Searcher {
+    matcher: Box<dyn Matcher>,
+    options: SearchOptions,
+    stats: SearchStats,
+    diagnostics: Box<dyn Diagnostics>,
+    control: Control,
 }
 
-impl<'a> SearcherBuilder<'a> {
-    pub(crate) fn new(config: &'a Config, re: &'a Regex) -> Self {
-        Self { config, re }
+impl Searcher {
+    /// Compiles the pattern in `options` into the built-in matcher.
+    pub fn new(options: SearchOptions) -> Result<Self> {
+        let matcher = options.matcher(options.regex()?);
+        Ok(Self::with_matcher(matcher, options))
     }
 
-    fn build_matcher(&self) -> Box<dyn Matcher + 'a> {
-        if self.config.search.only_matching {
-            Box::new(OnlyMatchingMatcher { re: self.re })
-        } else {
-            Box::new(DefaultMatcher {
-                re: self.re,
-                invert_match: self.config.search.invert_match,
-            })
+    /// Searches with a custom matcher. The pattern and matching flags in
+    /// `options` are ignored; context and sorting still apply.
+    pub fn with_matcher(matcher: Box<dyn Matcher>, options: SearchOptions) -> Self {
+        Self {
+            matcher,
+            options,
+            stats: SearchStats::default(),
+            diagnostics: Box::new(StderrDiagnostics),
+            control: Control::default(),
         }
     }
 
-    fn build_sink(
-        &self,
-        mode: OutputMode,
-        all_files: Option<HashSet<PathBuf>>,
-    ) -> Box<dyn Sink + 'a> {
-        match mode {
-            OutputMode::Standard => Box::new(StandardSink::new(self.config, self.re)),
-            OutputMode::Json => Box::new(JsonSink::default()),
-            OutputMode::Count => Box::new(CountSink::default()),
-            OutputMode::FilesWithMatches => Box::new(FilesWithMatchesSink::default()),
-            OutputMode::FilesWithoutMatch => {
-                let files = all_files.expect("List of all files is required for --files-without-match");
-                Box::new(FilesWithoutMatchSink::new(files))
-            }
-        }
+    /// Sends per-file problems to `diagnostics` instead of stderr.
+    pub fn diagnostics(mut self, diagnostics: impl Diagnostics + 'static) -> Self {
+        self.diagnostics = Box::new(diagnostics);
+        self
     }
 
-    pub(crate) fn build(
-        self,
-        mode: OutputMode,
-        all_files: Option<HashSet<PathBuf>>,
-    ) -> Searcher<'a> {
-        Searcher {
-            matcher: self.build_matcher(),
-            sink: self.build_sink(mode, all_files),
-        }
+    /// Stops searches once `token` is cancelled. They then return
+    /// [`Error::Cancelled`], leaving the sink unfinished.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.control.set_token(token);
+        self
     }
-}
 
-pub(crate) struct Searcher<'a> {
-    pub(crate) matcher: Box<dyn Matcher + 'a>,
-    pub(crate) sink: Box<dyn Sink + 'a>,
-}
+    /// Reports [`Progress`](crate::Progress) to `listener` as the search
+    /// goes. Counts accumulate across calls, like [`stats`](Self::stats).
+    pub fn progress(mut self, listener: impl ProgressListener + 'static) -> Self {
+        self.control.set_listener(Box::new(listener));
+        self
+    }
+
+    /// Counters accumulated over every search so far. Timings are left
+    /// for the caller to fill in.
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
 
-impl<'a> Searcher<'a> {
     fn search_stream<R: BufRead>(
         &mut self,
-        reader: R,
+        mut reader: R,
         path: &Path,
-        before_len: usize,
-        after_len: usize,
-    ) -> Result<(), Box<dyn Error>> {
+        sink: &mut dyn Sink,
+    ) -> Result<()> {
+        let (before_len, after_len) = self.options.context_lens();
+        sink.begin_file(path);
         let mut context_manager = ContextManager::new(
-            self.sink.as_mut(),
+            sink,
             before_len,
             after_len,
             path
         );
 
-        for (i, line_res) in reader.lines().enumerate() {
-            let line_num = i + 1;
-            let line_content = line_res?;
+        let mut buf = String::new();
+        let mut line_num = 0;
+        let mut byte_offset = 0;
+        let mut has_match = false;
+        self.stats.files_searched += 1;
 
-            if let Some(match_result) = self.matcher.find(&line_content) {
-                context_manager.handle_match(line_num, match_result)?;
+        loop {
+            buf.clear();
+            let bytes_read = match reader.read_line(&mut buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    return Err(Error::Encoding { path: path.to_path_buf(), line_number: line_num + 1 });
+                }
+                Err(e) => return Err(Error::io(path, e)),
+            };
+            if bytes_read == 0 {
+                break;
+            }
+            line_num += 1;
+            self.control.read(bytes_read)?;
+
+            let line_content = buf
+                .strip_suffix('\n')
+                .map(|l| l.strip_suffix('\r').unwrap_or(l))
+                .unwrap_or(&buf);
+
+            let flow = if let Some(match_result) = self.matcher.find(line_content) {
+                let spans = self.matcher.spans(line_content);
+                has_match = true;
+                self.stats.matched_lines += 1;
+                self.stats.matches += spans.len().max(1) as u64;
+                context_manager
+                    .handle_match(line_num, byte_offset, line_content, spans, match_result)
+                    .map_err(Error::Sink)?
             } else {
-                context_manager.handle_non_match(line_num, line_content)?;
+                context_manager
+                    .handle_non_match(line_num, line_content.to_string())
+                    .map_err(Error::Sink)?
+            };
+            byte_offset += bytes_read as u64;
+            if flow.is_break() {
+                break;
             }
         }
+
+        self.stats.bytes_searched += byte_offset;
+        if has_match {
+            self.stats.files_with_matches += 1;
+        }
+        self.control.file_done();
         Ok(())
     }
 
-    pub(crate) fn search_reader<R: BufRead>(
+    /// Searches everything read from `reader`, reporting it under `label`.
+    pub fn search_reader<R: Read>(
         &mut self,
+        label: impl AsRef<Path>,
         reader: R,
-        before_len: usize,
-        after_len: usize,
-    ) -> Result<(), Box<dyn Error>> {
-        self.search_stream(reader, Path::new("stdin"), before_len, after_len)
+        sink: &mut dyn Sink,
+    ) -> Result<()> {
+        self.control.check()?;
+        self.control.discovered();
+        self.search_stream(BufReader::new(reader), label.as_ref(), sink)
     }
 
-    pub(crate) fn search_path(
+    /// Searches an in-memory buffer, reporting it under `label`.
+    pub fn search_slice(
         &mut self,
-        path: &Path,
-        before_len: usize,
-        after_len: usize,
-    ) -> Result<(), Box<dyn Error>> {
-        let mut builder = WalkDir::new(path);
-        if path.is_dir() {
-            builder = builder.min_depth(1);
-        } else {
-            builder = builder.max_depth(0);
+        label: impl AsRef<Path>,
+        bytes: &[u8],
+        sink: &mut dyn Sink,
+    ) -> Result<()> {
+        self.control.check()?;
+        self.control.discovered();
+        self.search_stream(bytes, label.as_ref(), sink)
+    }
+
+    /// Searches a string, reporting it under `label`.
+    pub fn search_str(
+        &mut self,
+        label: impl AsRef<Path>,
+        text: &str,
+        sink: &mut dyn Sink,
+    ) -> Result<()> {
+        self.search_slice(label, text.as_bytes(), sink)
+    }
+
+    /// Searches every file `vfs` lists under `root`, as
+    /// [`search_path`](Self::search_path) does for the local disk. Files
+    /// are searched in the order `vfs` lists them, or by path when the
+    /// options sort by path; other sort keys need metadata a `Vfs` does not
+    /// provide and are ignored. Files that fail to open are reported to the
+    /// diagnostics channel.
+    ///
+    /// ```
+    /// use minigrep::{MemoryFs, SearchOptions, Searcher, Sink};
+    /// # use minigrep::MatchedLine;
+    /// # use std::{error::Error, ops::ControlFlow};
+    /// # struct Discard;
+    /// # impl Sink for Discard {
+    /// #     fn matched(&mut self, _: &MatchedLine<'_>) -> Result<ControlFlow<()>, Box<dyn Error + Send + Sync>> {
+    /// #         Ok(ControlFlow::Continue(()))
+    /// #     }
+    /// #     fn finish(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> { Ok(()) }
+    /// # }
+    ///
+    /// let mut vfs = MemoryFs::new();
+    /// vfs.insert("notes/a.txt", "todo: one\n");
+    /// vfs.insert("notes/b.txt", "done\ntodo: two\n");
+    ///
+    /// let mut searcher = Searcher::new(SearchOptions::new("todo")).unwrap();
+    /// searcher.search_vfs(&vfs, "notes", &mut Discard).unwrap();
+    /// assert_eq!(searcher.stats().files_with_matches, 2);
+    /// ```
+    pub fn search_vfs(
+        &mut self,
+        vfs: &dyn Vfs,
+        root: impl AsRef<Path>,
+        sink: &mut dyn Sink,
+    ) -> Result<()> {
+        let root = root.as_ref();
+        let mut files = vfs.files(root).map_err(|e| Error::io(root, e))?;
+        if let Some(sort) = self.options.file_sort().filter(|s| s.key == SortKey::Path) {
+            sort.sort(&mut files);
         }
-    
-        let walker = builder.into_iter()
-            .filter_entry(|e| !is_hidden(e));
-    
-        for entry_result in walker {
-            let entry = match entry_result {
-                Ok(e) => e,
+
+        for path in files {
+            self.control.check()?;
+            self.control.discovered();
+
+            let reader = match vfs.open(&path) {
+                Ok(reader) => BufReader::new(reader),
                 Err(e) => {
-                    eprintln!("Failed to access path: {}", e);
+                    self.diagnostics.report(&Error::io(&path, e));
+                    self.stats.files_skipped.record(SkipReason::Unreadable);
                     continue;
                 }
             };
-    
-            if !entry.file_type().is_file() {
-                continue;
-            }
-    
-            let file_path = entry.path();
-    
-            if is_binary(file_path).unwrap_or(true) {
-                continue;
+            match self.search_stream(reader, &path, sink) {
+                Err(e) if e.is_per_file() => self.diagnostics.report(&e),
+                result => result?,
             }
-            
+        }
+        Ok(())
+    }
+
+    /// Pulls matches one at a time instead of pushing them to a sink.
+    /// Searches the same files as [`search_path`](Self::search_path), but
+    /// yields per-file problems as errors rather than reporting them.
+    ///
+    /// ```
+    /// use minigrep::{SearchOptions, Searcher};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// std::fs::write(dir.path().join("a.txt"), "one\ntodo: two\nthree\n").unwrap();
+    ///
+    /// let mut searcher = Searcher::new(SearchOptions::new("todo").context(1)).unwrap();
+    /// let found: Vec<_> = searcher.matches(dir.path()).take(10).collect::<Result<_, _>>().unwrap();
+    /// assert_eq!(found.len(), 1);
+    /// assert_eq!(found[0].line_number, 2);
+    /// assert_eq!(found[0].before, ["one"]);
+    /// assert_eq!(found[0].after, ["three"]);
+    /// ```
+    pub fn matches(&mut self, path: impl AsRef<Path>) -> Matches<'_> {
+        let entries = sorted_walk(path.as_ref(), self.options.file_sort());
+        self.matches_from(entries)
+    }
+
+    /// [`matches`](Self::matches) over entries walked elsewhere, such as a
+    /// cached listing. They are searched in the order given.
+    pub(crate) fn matches_from(&mut self, entries: Box<dyn Iterator<Item = WalkEntry>>) -> Matches<'_> {
+        Matches::new(
+            self.matcher.as_ref(),
+            &mut self.stats,
+            &mut self.control,
+            entries,
+            self.options.context_lens(),
+        )
+    }
+
+    /// Searches a file, or every searchable file below a directory.
+    /// Hidden, ignored and binary files are skipped and counted in
+    /// [`stats`](Self::stats); files that cannot be read are reported to
+    /// the diagnostics channel.
+    pub fn search_path(
+        &mut self,
+        path: impl AsRef<Path>,
+        sink: &mut dyn Sink,
+    ) -> Result<()> {
+        for entry in sorted_walk(path.as_ref(), self.options.file_sort()) {
+            self.control.check()?;
+            let file_path = match entry {
+                WalkEntry::File(file_path) => {
+                    self.control.discovered();
+                    file_path
+                }
+                WalkEntry::Skipped(reason) => {
+                    self.stats.files_skipped.record(reason);
+                    continue;
+                }
+                WalkEntry::Pruned => {
+                    self.stats.dirs_pruned += 1;
+                    continue;
+                }
+                WalkEntry::Failed(e) => {
+                    self.diagnostics.report(&e);
+                    self.stats.files_skipped.record(SkipReason::Unreadable);
+                    continue;
+                }
+            };
+            let file_path = file_path.as_path();
+
             let file = match File::open(file_path) {
                 Ok(f) => f,
                 Err(e) => {
-                    eprintln!("Failed to open {}: {}", file_path.display(), e);
+                    self.diagnostics.report(&Error::io(file_path, e));
+                    self.stats.files_skipped.record(SkipReason::Unreadable);
                     continue;
                 }
             };
             let reader = BufReader::new(file);
-    
-            self.search_stream(reader, file_path, before_len, after_len)?;
+
+            match self.search_stream(reader, file_path, sink) {
+                Err(e) if e.is_per_file() => self.diagnostics.report(&e),
+                result => result?,
+            }
         }
         Ok(())
     }
-
 }
@@ -0,0 +1,115 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    error::{Error, Result},
+    fs::FileSort,
+    matcher::{impls::{DefaultMatcher, OnlyMatchingMatcher}, Matcher},
+};
+
+/// Everything that shapes a search, independent of how results are output.
+///
+/// ```
+/// use minigrep::SearchOptions;
+///
+/// let options = SearchOptions::new("fn main")
+///     .ignore_case(true)
+///     .context(2);
+/// assert_eq!(options.pattern(), "fn main");
+/// ```
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pattern: String,
+    ignore_case: bool,
+    invert_match: bool,
+    only_matching: bool,
+    before_context: usize,
+    after_context: usize,
+    sort: Option<FileSort>,
+}
+
+impl SearchOptions {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            ignore_case: false,
+            invert_match: false,
+            only_matching: false,
+            before_context: 0,
+            after_context: 0,
+            sort: None,
+        }
+    }
+
+    pub fn ignore_case(mut self, yes: bool) -> Self {
+        self.ignore_case = yes;
+        self
+    }
+
+    /// Select lines that do not match instead.
+    pub fn invert_match(mut self, yes: bool) -> Self {
+        self.invert_match = yes;
+        self
+    }
+
+    /// Report each occurrence rather than the whole line.
+    pub fn only_matching(mut self, yes: bool) -> Self {
+        self.only_matching = yes;
+        self
+    }
+
+    pub fn before_context(mut self, lines: usize) -> Self {
+        self.before_context = lines;
+        self
+    }
+
+    pub fn after_context(mut self, lines: usize) -> Self {
+        self.after_context = lines;
+        self
+    }
+
+    /// Sets both the before and after context.
+    pub fn context(self, lines: usize) -> Self {
+        self.before_context(lines).after_context(lines)
+    }
+
+    /// Order in which files under a directory are searched. Walk order
+    /// is used when unset.
+    pub fn sort(mut self, sort: Option<FileSort>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub(crate) fn context_lens(&self) -> (usize, usize) {
+        (self.before_context, self.after_context)
+    }
+
+    pub(crate) fn file_sort(&self) -> Option<FileSort> {
+        self.sort
+    }
+
+    /// Compiles the pattern with these options.
+    pub fn regex(&self) -> Result<Regex> {
+        RegexBuilder::new(&self.pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map_err(|e| Error::pattern(&self.pattern, self.ignore_case, e))
+    }
+
+    /// The built-in matcher these options call for, using `re`.
+    pub fn matcher(&self, re: Regex) -> Box<dyn Matcher> {
+        self.shared_matcher(re)
+    }
+
+    /// [`matcher`](Self::matcher) for callers that move it across threads.
+    pub(crate) fn shared_matcher(&self, re: Regex) -> Box<dyn Matcher + Send + Sync> {
+        if self.only_matching {
+            Box::new(OnlyMatchingMatcher::new(re))
+        } else {
+            Box::new(DefaultMatcher::new(re, self.invert_match))
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{fmt, time::Duration};
+
+use serde::{Serialize, Serializer};
+
+use crate::fs::SkipReason;
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SkippedFiles {
+    pub hidden: u64,
+    pub binary: u64,
+    pub ignored: u64,
+    pub unreadable: u64,
+}
+
+impl SkippedFiles {
+    pub fn record(&mut self, reason: SkipReason) {
+        match reason {
+            SkipReason::Hidden => self.hidden += 1,
+            SkipReason::Binary => self.binary += 1,
+            SkipReason::Ignored => self.ignored += 1,
+            SkipReason::Unreadable => self.unreadable += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.hidden + self.binary + self.ignored + self.unreadable
+    }
+}
+
+/// Counters gathered over one run, reported by `--stats`.
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchStats {
+    pub files_searched: u64,
+    pub files_skipped: SkippedFiles,
+    /// Hidden and ignored directories that were not descended into; the
+    /// files inside them are in neither count above.
+    pub dirs_pruned: u64,
+    pub bytes_searched: u64,
+    pub matched_lines: u64,
+    pub matches: u64,
+    pub files_with_matches: u64,
+    #[serde(rename = "elapsed_wall_secs", serialize_with = "serialize_secs")]
+    pub elapsed_wall: Duration,
+    #[serde(rename = "elapsed_cpu_secs", serialize_with = "serialize_optional_secs")]
+    pub elapsed_cpu: Option<Duration>,
+}
+
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn serialize_optional_secs<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(d) => serializer.serialize_f64(d.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let skipped = &self.files_skipped;
+        writeln!(f, "Files searched:     {}", self.files_searched)?;
+        writeln!(
+            f,
+            "Files skipped:      {} (hidden {}, binary {}, ignored {}, unreadable {})",
+            skipped.total(),
+            skipped.hidden,
+            skipped.binary,
+            skipped.ignored,
+            skipped.unreadable
+        )?;
+        writeln!(f, "Dirs pruned:        {} (hidden or ignored, not descended into)", self.dirs_pruned)?;
+        writeln!(f, "Bytes searched:     {}", self.bytes_searched)?;
+        writeln!(f, "Matched lines:      {}", self.matched_lines)?;
+        writeln!(f, "Matches:            {}", self.matches)?;
+        writeln!(f, "Files with matches: {}", self.files_with_matches)?;
+        write!(f, "Elapsed:            {:.3}s wall", self.elapsed_wall.as_secs_f64())?;
+        if let Some(cpu) = self.elapsed_cpu {
+            write!(f, ", {:.3}s cpu", cpu.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// User plus system CPU time consumed by this process so far.
+#[cfg(unix)]
+pub fn cpu_time() -> Option<Duration> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: `getrusage` fully initializes `usage` when it returns 0.
+    let usage = unsafe {
+        if libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) != 0 {
+            return None;
+        }
+        usage.assume_init()
+    };
+    let to_duration = |t: libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+    Some(to_duration(usage.ru_utime) + to_duration(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+pub fn cpu_time() -> Option<Duration> {
+    None
+}
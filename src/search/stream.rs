@@ -0,0 +1,180 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    pin::Pin,
+    str,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::io::{AsyncBufRead, AsyncRead, BufReader};
+
+use crate::{
+    error::{Error, Result},
+    matcher::Matcher,
+    search::{
+        matches::{Match, MatchCollector},
+        options::SearchOptions,
+        stats::SearchStats,
+    },
+};
+
+/// Searches `reader` without blocking the runtime, yielding matches as
+/// they are found and reporting them under `label`. Pass a
+/// `tokio::fs::File` to search a file. Dropping the stream stops the
+/// search.
+///
+/// ```
+/// use minigrep::{search_async, SearchOptions};
+/// use tokio_stream::StreamExt;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let input: &[u8] = b"one\ntodo: two\nthree\n";
+/// let mut found = search_async(&SearchOptions::new("todo"), "notes.txt", input).unwrap();
+/// while let Some(m) = found.next().await {
+///     assert_eq!(m.unwrap().line_number, 2);
+/// }
+/// assert_eq!(found.stats().matched_lines, 1);
+/// # });
+/// ```
+pub fn search_async<R: AsyncRead + Unpin>(
+    options: &SearchOptions,
+    label: impl Into<PathBuf>,
+    reader: R,
+) -> Result<AsyncMatches<R>> {
+    let matcher = options.shared_matcher(options.regex()?);
+    let stats = SearchStats { files_searched: 1, ..SearchStats::default() };
+    Ok(AsyncMatches {
+        matcher,
+        reader: BufReader::new(reader),
+        line: Vec::new(),
+        collector: Some(MatchCollector::new(label.into(), options.context_lens())),
+        stats,
+        ready: VecDeque::new(),
+    })
+}
+
+/// How many reads [`AsyncMatches`] makes in one poll before yielding to
+/// the runtime, so an always-ready reader cannot starve other tasks.
+const READS_PER_POLL: usize = 1024;
+
+/// Stream returned by [`search_async`]. A read or decoding error is
+/// yielded once and ends the stream.
+pub struct AsyncMatches<R> {
+    matcher: Box<dyn Matcher + Send + Sync>,
+    reader: BufReader<R>,
+    /// The line read so far, which may span several buffer fills.
+    line: Vec<u8>,
+    /// `None` once the input is exhausted or has failed.
+    collector: Option<MatchCollector>,
+    stats: SearchStats,
+    ready: VecDeque<Match>,
+}
+
+impl<R> AsyncMatches<R> {
+    /// Counters for the input read so far.
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    /// Hands the buffered line to the collector.
+    fn take_line(&mut self) -> Result<()> {
+        let Some(collector) = self.collector.as_mut() else {
+            return Ok(());
+        };
+        let Ok(text) = str::from_utf8(&self.line) else {
+            return Err(collector.encoding_error());
+        };
+        collector.line(text, self.line.len(), self.matcher.as_ref(), &mut self.stats, &mut self.ready);
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncMatches<R> {
+    type Item = Result<Match>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        for _ in 0..READS_PER_POLL {
+            if let Some(found) = this.ready.pop_front() {
+                return Poll::Ready(Some(Ok(found)));
+            }
+            let Some(collector) = this.collector.as_ref() else {
+                return Poll::Ready(None);
+            };
+
+            let available = match ready!(Pin::new(&mut this.reader).poll_fill_buf(cx)) {
+                Ok(available) => available,
+                Err(e) => {
+                    let path = collector.path().clone();
+                    this.collector = None;
+                    return Poll::Ready(Some(Err(Error::io(path, e))));
+                }
+            };
+
+            let result = if available.is_empty() {
+                let result = if this.line.is_empty() { Ok(()) } else { this.take_line() };
+                if let Some(collector) = this.collector.take() {
+                    collector.finish(&mut this.stats, &mut this.ready);
+                }
+                result
+            } else {
+                let (consumed, complete) = match available.iter().position(|&b| b == b'\n') {
+                    Some(newline) => (newline + 1, true),
+                    None => (available.len(), false),
+                };
+                this.line.extend_from_slice(&available[..consumed]);
+                Pin::new(&mut this.reader).consume(consumed);
+                if complete { this.take_line() } else { Ok(()) }
+            };
+
+            if let Err(e) = result {
+                this.collector = None;
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+        if let Some(found) = this.ready.pop_front() {
+            return Poll::Ready(Some(Ok(found)));
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_context_and_encoding_error() {
+        let input: &[u8] = b"a\nhit\nb\nhit\n\xff\n";
+        let options = SearchOptions::new("hit").context(1);
+        let results: Vec<_> = search_async(&options, "input", input).unwrap().collect().await;
+
+        assert_eq!(results.len(), 2);
+        let first = results[0].as_ref().unwrap();
+        assert_eq!(first.line_number, 2);
+        assert_eq!((first.before.as_slice(), first.after.as_slice()), (&["a".to_string()][..], &["b".to_string()][..]));
+        assert!(matches!(results[1], Err(Error::Encoding { line_number: 5, .. })));
+    }
+
+    #[test]
+    fn test_yields_to_runtime_on_ready_input() {
+        let mut input = "miss\n".repeat(READS_PER_POLL * 2);
+        input.push_str("hit\n");
+        let mut found = search_async(&SearchOptions::new("hit"), "input", input.as_bytes()).unwrap();
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+
+        let mut polls = 1;
+        let result = loop {
+            match Pin::new(&mut found).poll_next(&mut cx) {
+                Poll::Ready(result) => break result,
+                Poll::Pending => polls += 1,
+            }
+        };
+        assert!(polls > 1);
+        assert_eq!(result.unwrap().unwrap().line_number, READS_PER_POLL * 2 + 1);
+    }
+}
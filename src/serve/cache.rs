@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    fs, iter,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
+};
+
+use regex::Regex;
+
+use crate::{
+    error::{Error, Result},
+    fs::{walk, FileSort, SkipReason, WalkEntry},
+    request::Request,
+};
+
+/// Compiled patterns kept before the cache is emptied, so that a client
+/// searching as the user types does not grow it without bound.
+const MAX_PATTERNS: usize = 256;
+
+/// State kept warm between searches: compiled patterns, and the files
+/// found under each searched path.
+#[derive(Default)]
+pub(crate) struct Caches {
+    patterns: Mutex<HashMap<(String, bool), Regex>>,
+    listings: Mutex<HashMap<PathBuf, Arc<Listing>>>,
+}
+
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Caches {
+    pub(crate) fn regex(&self, request: &Request) -> Result<Regex> {
+        let key = (request.query(), request.ignore_case());
+        if let Some(re) = lock(&self.patterns).get(&key) {
+            return Ok(re.clone());
+        }
+
+        let re = request.compile()?;
+        let mut patterns = lock(&self.patterns);
+        if patterns.len() >= MAX_PATTERNS {
+            patterns.clear();
+        }
+        patterns.insert(key, re.clone());
+        Ok(re)
+    }
+
+    /// The entries under `root` in `sort` order. The previous walk is
+    /// reused while no directory in it has been modified since; walks that
+    /// hit errors are not kept.
+    pub(crate) fn entries(&self, root: &Path, sort: Option<FileSort>) -> Box<dyn Iterator<Item = WalkEntry>> {
+        let cached = lock(&self.listings).get(root).cloned();
+        if let Some(listing) = cached.filter(|listing| listing.is_fresh()) {
+            return listing.entries(sort);
+        }
+
+        let (listing, failures) = Listing::walk(root);
+        let entries = listing.entries(sort);
+        if failures.is_empty() {
+            lock(&self.listings).insert(root.to_path_buf(), Arc::new(listing));
+        }
+        Box::new(failures.into_iter().map(WalkEntry::Failed).chain(entries))
+    }
+}
+
+/// The outcome of walking one path.
+struct Listing {
+    files: Vec<PathBuf>,
+    skipped: Vec<SkipReason>,
+    pruned: usize,
+    /// The root and every directory below it, with when each was last
+    /// modified. Adding, removing or renaming a file changes its directory.
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Listing {
+    fn walk(root: &Path) -> (Self, Vec<Error>) {
+        let mut walk = walk(root).record_dirs();
+        let mut files = Vec::new();
+        let mut skipped = Vec::new();
+        let mut pruned = 0;
+        let mut failures = Vec::new();
+        for entry in walk.by_ref() {
+            match entry {
+                WalkEntry::File(path) => files.push(path),
+                WalkEntry::Skipped(reason) => skipped.push(reason),
+                WalkEntry::Pruned => pruned += 1,
+                WalkEntry::Failed(e) => failures.push(e),
+            }
+        }
+
+        let dirs = iter::once(root)
+            .chain(walk.dirs().iter().map(PathBuf::as_path))
+            .map(|dir| (dir.to_path_buf(), modified(dir)))
+            .collect();
+        (Self { files, skipped, pruned, dirs }, failures)
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.dirs.iter().all(|(dir, when)| when.is_some() && modified(dir) == *when)
+    }
+
+    /// Skipped and pruned entries first, then the files, as a sorted walk
+    /// yields them.
+    fn entries(&self, sort: Option<FileSort>) -> Box<dyn Iterator<Item = WalkEntry>> {
+        let mut files = self.files.clone();
+        if let Some(sort) = sort {
+            sort.sort(&mut files);
+        }
+        let skipped = self.skipped.clone().into_iter().map(WalkEntry::Skipped);
+        let pruned = iter::repeat_with(|| WalkEntry::Pruned).take(self.pruned);
+        Box::new(skipped.chain(pruned).chain(files.into_iter().map(WalkEntry::File)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(caches: &Caches, root: &Path) -> usize {
+        caches.entries(root, None).filter(|entry| matches!(entry, WalkEntry::File(_))).count()
+    }
+
+    #[test]
+    fn test_listing_refreshes_when_a_directory_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/a.txt"), "a\n").unwrap();
+
+        let caches = Caches::default();
+        assert_eq!(files(&caches, dir.path()), 1);
+        assert!(lock(&caches.listings).contains_key(dir.path()));
+
+        fs::write(dir.path().join("sub/b.txt"), "b\n").unwrap();
+        // Directory timestamps can be coarse; make sure the change shows.
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::open(dir.path().join("sub")).unwrap().set_modified(later).unwrap();
+        assert_eq!(files(&caches, dir.path()), 2);
+    }
+}
@@ -0,0 +1,529 @@
+// src/serve/mod.rs
+
+//! `minigrep --serve`: JSON-RPC 2.0 over stdin and stdout, one message per
+//! line. Methods:
+//!
+//! - `search`: params are a `--request` document without `mode`. Matches
+//!   arrive in `search/matches` notifications ({"id", "matches"}) and
+//!   per-file problems in `search/diagnostic` ({"id", "message"}); the
+//!   response carries the search's stats. Each search runs on its own
+//!   thread.
+//! - `cancel`: params {"id"} name a running search, which then fails with
+//!   code -32800.
+//! - `shutdown`: cancels every search and exits once they have answered.
+
+mod cache;
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    ops::ControlFlow,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    error::{Error, Result},
+    request::Request,
+    config::Config,
+    search::{control::CancellationToken, matches::Match, stats::SearchStats, Searcher},
+};
+
+use self::cache::{lock, Caches};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// Matches are sent once this many are waiting, or once the oldest has
+/// waited [`BATCH_INTERVAL`], even if the search is still busy elsewhere.
+const BATCH_SIZE: usize = 100;
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Deserialize)]
+struct Call {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+struct Server {
+    caches: Caches,
+    /// Cancellation tokens of running searches, keyed by request id.
+    running: Mutex<HashMap<String, CancellationToken>>,
+    /// Where messages go. Holding the lock while writing keeps messages
+    /// from concurrent searches whole.
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+pub(crate) fn run() -> Result<()> {
+    let server = Arc::new(Server::new(Box::new(io::stdout())));
+    let mut workers: Vec<JoinHandle<()>> = Vec::new();
+
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| Error::io("stdin", e))?;
+        workers.retain(|worker| !worker.is_finished());
+        match server.handle(&line) {
+            Ok(ControlFlow::Continue(worker)) => workers.extend(worker),
+            Ok(ControlFlow::Break(())) => break,
+            Err(e) => {
+                server.cancel_all();
+                return Err(Error::io("stdout", e));
+            }
+        }
+    }
+
+    server.cancel_all();
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+impl Server {
+    fn new(out: Box<dyn Write + Send>) -> Self {
+        Self { caches: Caches::default(), running: Mutex::default(), out: Mutex::new(out) }
+    }
+
+    /// Answers one line from the client, returning the thread of any
+    /// search it started.
+    fn handle(self: &Arc<Self>, line: &str) -> io::Result<ControlFlow<(), Option<JoinHandle<()>>>> {
+        if line.trim().is_empty() {
+            return Ok(ControlFlow::Continue(None));
+        }
+        let call = match serde_json::from_str::<Value>(line) {
+            Err(e) => {
+                self.send_error(&Value::Null, PARSE_ERROR, &e.to_string(), None)?;
+                return Ok(ControlFlow::Continue(None));
+            }
+            Ok(value) => match serde_json::from_value::<Call>(value) {
+                Ok(call) => call,
+                Err(e) => {
+                    self.send_error(&Value::Null, INVALID_REQUEST, &e.to_string(), None)?;
+                    return Ok(ControlFlow::Continue(None));
+                }
+            },
+        };
+
+        // Calls without an id are notifications and get no reply.
+        let Some(id) = call.id else {
+            return Ok(match call.method.as_str() {
+                "cancel" => {
+                    self.cancel(&call.params);
+                    ControlFlow::Continue(None)
+                }
+                "shutdown" => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(None),
+            });
+        };
+
+        match call.method.as_str() {
+            "search" => Ok(ControlFlow::Continue(self.start_search(id, call.params)?)),
+            "cancel" => {
+                if call.params.get("id").is_none() {
+                    self.send_error(&id, INVALID_PARAMS, "cancel needs the id of a search", None)?;
+                } else {
+                    let cancelled = self.cancel(&call.params);
+                    self.respond(&id, json!({ "cancelled": cancelled }))?;
+                }
+                Ok(ControlFlow::Continue(None))
+            }
+            "shutdown" => {
+                self.respond(&id, Value::Null)?;
+                Ok(ControlFlow::Break(()))
+            }
+            method => {
+                self.send_error(&id, METHOD_NOT_FOUND, &format!("unknown method '{}'", method), None)?;
+                Ok(ControlFlow::Continue(None))
+            }
+        }
+    }
+
+    fn start_search(self: &Arc<Self>, id: Value, params: Value) -> io::Result<Option<JoinHandle<()>>> {
+        let prepared = Request::from_value(params).and_then(|request| {
+            request.require_matches_mode()?;
+            let re = self.caches.regex(&request)?;
+            let paths = request.paths();
+            Ok((request.into_config()?, paths, re))
+        });
+        let (config, paths, re) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                let data = match &e {
+                    Error::Request { field, .. } if !field.is_empty() => Some(json!({ "field": field })),
+                    _ => None,
+                };
+                self.send_error(&id, INVALID_PARAMS, &e.to_string(), data)?;
+                return Ok(None);
+            }
+        };
+
+        let key = id.to_string();
+        let token = CancellationToken::new();
+        {
+            let mut running = lock(&self.running);
+            if running.contains_key(&key) {
+                drop(running);
+                self.send_error(&id, INVALID_REQUEST, &format!("a search with id {} is already running", key), None)?;
+                return Ok(None);
+            }
+            running.insert(key.clone(), token.clone());
+        }
+
+        let server = Arc::clone(self);
+        Ok(Some(thread::spawn(move || {
+            let outcome = server.search(&id, &config, &paths, re, token);
+            lock(&server.running).remove(&key);
+            // A failed write means the client has gone; the main loop
+            // finds out on its next read or write.
+            let _ = match outcome {
+                Ok(Some(stats)) => server.respond(&id, json!({ "stats": stats })),
+                Ok(None) => server.send_error(&id, REQUEST_CANCELLED, &Error::Cancelled.to_string(), None),
+                Err(e) => Err(e),
+            };
+        })))
+    }
+
+    /// Runs one search, streaming its matches. Returns `None` if it was
+    /// cancelled.
+    fn search(
+        &self,
+        id: &Value,
+        config: &Config,
+        paths: &[PathBuf],
+        re: regex::Regex,
+        token: CancellationToken,
+    ) -> io::Result<Option<SearchStats>> {
+        let started = Instant::now();
+        let options = config.search_options();
+        let mut searcher = Searcher::with_matcher(options.matcher(re), options.clone()).cancellation(token);
+
+        // Bounded, so a client that reads slowly slows the search down
+        // rather than letting matches pile up.
+        let (matches, batches) = mpsc::sync_channel(BATCH_SIZE);
+        let cancelled = thread::scope(|scope| {
+            let sender = scope.spawn(|| self.send_batches(id, batches));
+            let cancelled = self.find(id, config, paths, &mut searcher, matches);
+            // `matches` has been dropped, so the sender sends what is left
+            // and stops. If it failed first, its error is the one to report.
+            sender.join().expect("batch sender panicked")?;
+            cancelled
+        })?;
+        if cancelled {
+            return Ok(None);
+        }
+
+        let mut stats = searcher.stats().clone();
+        stats.elapsed_wall = started.elapsed();
+        Ok(Some(stats))
+    }
+
+    /// Feeds the matches under every path to `matches`, reporting per-file
+    /// problems as they come. Returns whether the search was cancelled.
+    fn find(
+        &self,
+        id: &Value,
+        config: &Config,
+        paths: &[PathBuf],
+        searcher: &mut Searcher,
+        matches: SyncSender<Match>,
+    ) -> io::Result<bool> {
+        let sort = config.search_options().file_sort();
+        for path in paths {
+            let entries = self.caches.entries(path, sort);
+            for item in searcher.matches_from(entries) {
+                match item {
+                    Ok(found) => {
+                        if matches.send(found).is_err() {
+                            // The sender has stopped on a failed write.
+                            return Ok(false);
+                        }
+                    }
+                    Err(Error::Cancelled) => return Ok(true),
+                    Err(_) if config.output.no_messages => {}
+                    Err(e) => {
+                        self.notify("search/diagnostic", json!({ "id": id, "message": e.to_string() }))?;
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Sends the matches arriving on `matches` in batches, until the search
+    /// drops its end of the channel.
+    fn send_batches(&self, id: &Value, matches: Receiver<Match>) -> io::Result<()> {
+        let mut batch = Vec::new();
+        // When the oldest match in `batch` has waited long enough.
+        let mut due: Option<Instant> = None;
+        loop {
+            let received = match due {
+                Some(due) => matches.recv_timeout(due.saturating_duration_since(Instant::now())),
+                None => matches.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(found) => {
+                    due.get_or_insert_with(|| Instant::now() + BATCH_INTERVAL);
+                    batch.push(found);
+                    if batch.len() < BATCH_SIZE {
+                        continue;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return self.flush(id, &mut batch),
+            }
+            self.flush(id, &mut batch)?;
+            due = None;
+        }
+    }
+
+    fn flush(&self, id: &Value, batch: &mut Vec<Match>) -> io::Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let matches = std::mem::take(batch);
+        self.notify("search/matches", json!({ "id": id, "matches": matches }))
+    }
+
+    /// Cancels the search named by `params.id`, if it is still running.
+    fn cancel(&self, params: &Value) -> bool {
+        let Some(id) = params.get("id") else {
+            return false;
+        };
+        match lock(&self.running).get(&id.to_string()) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn cancel_all(&self) {
+        lock(&self.running).values().for_each(CancellationToken::cancel);
+    }
+
+    /// Writes one message as a line.
+    fn send(&self, message: &Value) -> io::Result<()> {
+        let mut out = lock(&self.out);
+        serde_json::to_writer(&mut *out, message).map_err(io::Error::from)?;
+        out.write_all(b"\n")?;
+        out.flush()
+    }
+
+    fn respond(&self, id: &Value, result: Value) -> io::Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    fn send_error(&self, id: &Value, code: i64, message: &str, data: Option<Value>) -> io::Result<()> {
+        let mut error = json!({ "code": code, "message": message });
+        if let Some(data) = data {
+            error["data"] = data;
+        }
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "error": error }))
+    }
+
+    fn notify(&self, method: &str, params: Value) -> io::Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, sync::Condvar, thread::ThreadId};
+
+    use super::*;
+
+    /// Collects what the server writes. While the gate is shut, writes from
+    /// threads other than the test's own block, holding a search mid-way.
+    #[derive(Clone)]
+    struct Output {
+        written: Arc<Mutex<Vec<u8>>>,
+        gate: Arc<(Mutex<bool>, Condvar)>,
+        owner: ThreadId,
+    }
+
+    impl Output {
+        fn new(open: bool) -> Self {
+            Self {
+                written: Arc::default(),
+                gate: Arc::new((Mutex::new(open), Condvar::new())),
+                owner: thread::current().id(),
+            }
+        }
+
+        fn open(&self) {
+            *lock(&self.gate.0) = true;
+            self.gate.1.notify_all();
+        }
+
+        fn messages(&self) -> Vec<Value> {
+            let written = lock(&self.written);
+            written
+                .split(|&b| b == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(|line| serde_json::from_slice(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if thread::current().id() != self.owner {
+                let (open, opened) = &*self.gate;
+                drop(opened.wait_while(lock(open), |open| !*open).unwrap());
+            }
+            lock(&self.written).extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn call(server: &Arc<Server>, message: Value) -> ControlFlow<(), Option<JoinHandle<()>>> {
+        server.handle(&message.to_string()).unwrap()
+    }
+
+    fn search(id: u64, root: &Path) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "search",
+            "params": { "version": 1, "patterns": ["foo"], "paths": [root] },
+        })
+    }
+
+    fn run_search(server: &Arc<Server>, id: u64, root: &Path) {
+        let ControlFlow::Continue(Some(worker)) = call(server, search(id, root)) else {
+            panic!("the search did not start");
+        };
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn test_search_streams_matches_then_answers_with_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "foo\nbar\nfoo\n").unwrap();
+        let output = Output::new(true);
+        let server = Arc::new(Server::new(Box::new(output.clone())));
+
+        run_search(&server, 1, dir.path());
+
+        let messages = output.messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["method"], "search/matches");
+        assert_eq!(messages[0]["params"]["id"], 1);
+        assert_eq!(messages[0]["params"]["matches"].as_array().unwrap().len(), 2);
+        assert_eq!(messages[1]["id"], 1);
+        assert!(messages[1]["result"]["stats"].is_object());
+    }
+
+    #[test]
+    fn test_cancel_fails_the_search_with_request_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        // Far more matches than the sender and the channel hold, so the
+        // search is stuck early in the file while the output is held, and
+        // reads well past a cancellation check once it is let go.
+        fs::write(dir.path().join("a.txt"), "foo\n".repeat(20_000)).unwrap();
+        let output = Output::new(false);
+        let server = Arc::new(Server::new(Box::new(output.clone())));
+
+        let ControlFlow::Continue(Some(worker)) = call(&server, search(7, dir.path())) else {
+            panic!("the search did not start");
+        };
+        assert!(call(&server, json!({ "jsonrpc": "2.0", "method": "cancel", "params": { "id": 7 } })).is_continue());
+        output.open();
+        worker.join().unwrap();
+
+        let last = output.messages().pop().unwrap();
+        assert_eq!(last["id"], 7);
+        assert_eq!(last["error"]["code"], REQUEST_CANCELLED);
+
+        // Once it has answered, the search can no longer be cancelled.
+        assert!(call(&server, json!({ "jsonrpc": "2.0", "id": 8, "method": "cancel", "params": { "id": 7 } })).is_continue());
+        assert_eq!(output.messages().pop().unwrap()["result"], json!({ "cancelled": false }));
+    }
+
+    #[test]
+    fn test_cancel_reports_whether_a_search_was_running() {
+        let output = Output::new(true);
+        let server = Arc::new(Server::new(Box::new(output.clone())));
+        let token = CancellationToken::new();
+        lock(&server.running).insert("3".to_string(), token.clone());
+
+        assert!(call(&server, json!({ "jsonrpc": "2.0", "id": 4, "method": "cancel", "params": { "id": 3 } })).is_continue());
+        assert!(token.is_cancelled());
+        assert_eq!(output.messages()[0]["result"], json!({ "cancelled": true }));
+
+        assert!(call(&server, json!({ "jsonrpc": "2.0", "id": 5, "method": "cancel", "params": {} })).is_continue());
+        assert_eq!(output.messages()[1]["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_duplicate_id_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = Output::new(true);
+        let server = Arc::new(Server::new(Box::new(output.clone())));
+        lock(&server.running).insert("3".to_string(), CancellationToken::new());
+
+        let ControlFlow::Continue(None) = call(&server, search(3, dir.path())) else {
+            panic!("a second search with the same id started");
+        };
+        let reply = &output.messages()[0];
+        assert_eq!(reply["id"], 3);
+        assert_eq!(reply["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_shutdown_answers_then_stops() {
+        let output = Output::new(true);
+        let server = Arc::new(Server::new(Box::new(output.clone())));
+
+        assert!(call(&server, json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" })).is_break());
+        assert_eq!(output.messages(), [json!({ "jsonrpc": "2.0", "id": 1, "result": null })]);
+
+        assert!(call(&server, json!({ "jsonrpc": "2.0", "method": "shutdown" })).is_break());
+        assert_eq!(output.messages().len(), 1);
+    }
+
+    #[test]
+    fn test_waiting_matches_are_sent_without_more_arriving() {
+        let output = Output::new(true);
+        let server = Server::new(Box::new(output.clone()));
+        let id = json!(1);
+        let (matches, batches) = mpsc::sync_channel(BATCH_SIZE);
+
+        thread::scope(|scope| {
+            let sender = scope.spawn(|| server.send_batches(&id, batches));
+            let found = Match {
+                path: PathBuf::from("a.txt"),
+                line_number: 1,
+                byte_offset: 0,
+                line: "foo".to_string(),
+                spans: Vec::new(),
+                before: Vec::new(),
+                after: Vec::new(),
+            };
+            matches.send(found).unwrap();
+            thread::sleep(BATCH_INTERVAL * 4);
+            // Still searching, as far as the sender knows.
+            assert_eq!(output.messages().len(), 1);
+            drop(matches);
+            sender.join().unwrap().unwrap();
+        });
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+/// A source of files other than the local disk, such as a database or an
+/// archive, for [`Searcher::search_vfs`](crate::Searcher::search_vfs).
+pub trait Vfs {
+    /// Every file at or below `root`, in the order they should be searched.
+    fn files(&self, root: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Opens one of the paths returned by [`files`](Self::files).
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+}
+
+/// A [`Vfs`] held in memory, listing files by path. `.` components are
+/// ignored when matching a root, so `.` and the empty path list every file.
+///
+/// ```
+/// use minigrep::{MemoryFs, Vfs};
+/// use std::path::Path;
+///
+/// let mut vfs = MemoryFs::new();
+/// vfs.insert("docs/a.md", "# A\n");
+/// vfs.insert("src/main.rs", "fn main() {}\n");
+/// assert_eq!(vfs.files(Path::new("src")).unwrap(), [Path::new("src/main.rs")]);
+/// assert_eq!(vfs.files(Path::new("./src")).unwrap(), [Path::new("src/main.rs")]);
+/// assert_eq!(vfs.files(Path::new(".")).unwrap().len(), 2);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces a file.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(path.into(), contents.into());
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn files(&self, root: &Path) -> io::Result<Vec<PathBuf>> {
+        let root = without_cur_dir(root);
+        Ok(self.files.keys().filter(|path| without_cur_dir(path).starts_with(&root)).cloned().collect())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match self.files.get(path) {
+            Some(contents) => Ok(Box::new(contents.as_slice())),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no such file")),
+        }
+    }
+}
+
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components().filter(|c| *c != Component::CurDir).collect()
+}